@@ -0,0 +1,105 @@
+import Gio from 'gi://Gio';
+import Meta from 'gi://Meta';
+import * as Main from 'resource:///org/gnome/shell/ui/main.js';
+import {Extension} from 'resource:///org/gnome/shell/extensions/extension.js';
+
+const BUS_NAME = 'io.github.iondodon.Witcher.Shell';
+const OBJECT_PATH = '/io/github/iondodon/Witcher/Shell';
+const INTERFACE = `
+<node>
+  <interface name="io.github.iondodon.Witcher.Shell">
+    <method name="ListWindows">
+      <arg type="s" direction="out" name="windows"/>
+    </method>
+    <method name="ActivateWindow">
+      <arg type="t" direction="in" name="id"/>
+      <arg type="b" direction="out" name="activated"/>
+    </method>
+    <method name="CloseWindow">
+      <arg type="t" direction="in" name="id"/>
+      <arg type="b" direction="out" name="closed"/>
+    </method>
+    <method name="MinimizeWindow">
+      <arg type="t" direction="in" name="id"/>
+      <arg type="b" direction="out" name="minimized"/>
+    </method>
+    <method name="FocusedMonitor">
+      <arg type="i" direction="out" name="width"/>
+      <arg type="i" direction="out" name="height"/>
+      <arg type="d" direction="out" name="scale"/>
+    </method>
+  </interface>
+</node>`;
+
+function switchableWindows() {
+    return global.display.get_tab_list(Meta.TabList.NORMAL_ALL, null);
+}
+
+function findWindow(id) {
+    return switchableWindows().find(window => window.get_id() === Number(id));
+}
+
+class WitcherService {
+    ListWindows() {
+        const focused = global.display.focus_window;
+        const windows = switchableWindows().map(window => ({
+            id: window.get_id(),
+            wm_class: window.get_wm_class(),
+            title: window.get_title(),
+            pid: window.get_pid() > 0 ? window.get_pid() : null,
+            focused: window === focused,
+        }));
+        return JSON.stringify(windows);
+    }
+
+    ActivateWindow(id) {
+        const window = findWindow(id);
+        if (!window) {
+            return false;
+        }
+        Main.activateWindow(window);
+        return true;
+    }
+
+    CloseWindow(id) {
+        const window = findWindow(id);
+        if (!window) {
+            return false;
+        }
+        window.delete(global.get_current_time());
+        return true;
+    }
+
+    MinimizeWindow(id) {
+        const window = findWindow(id);
+        if (!window || !window.can_minimize()) {
+            return false;
+        }
+        window.minimize();
+        return true;
+    }
+
+    FocusedMonitor() {
+        const index = global.display.get_current_monitor();
+        const geometry = global.display.get_monitor_geometry(index);
+        const scale = global.display.get_monitor_scale(index);
+        return [geometry.width, geometry.height, scale];
+    }
+}
+
+export default class WitcherExtension extends Extension {
+    enable() {
+        this._service = new WitcherService();
+        this._exported = Gio.DBusExportedObject.wrapJSObject(INTERFACE, this._service);
+        this._exported.export(Gio.DBus.session, OBJECT_PATH);
+        this._ownerId = Gio.bus_own_name_on_connection(
+            Gio.DBus.session, BUS_NAME, Gio.BusNameOwnerFlags.NONE, null, null);
+    }
+
+    disable() {
+        Gio.bus_unown_name(this._ownerId);
+        this._exported.unexport();
+        this._exported = null;
+        this._service = null;
+    }
+}
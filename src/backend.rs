@@ -1,10 +1,126 @@
 use anyhow::{Context, Result};
-use niri_ipc::{socket::Socket, Action, Request, Response};
-use serde::Deserialize;
-use std::process::Command;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+    mpsc::Sender,
+};
 
-use crate::types::BackendKind;
+use crate::types::{BackendKind, WindowFilter};
 
+mod foreign_toplevel;
+mod gnome;
+mod hyprland;
+mod kwin;
+mod niri;
+mod sway;
+#[cfg(test)]
+mod test_bus;
+
+/// A compositor connection owned by the daemon for its whole lifetime.
+///
+/// Implementations keep their IPC connections open and cache what they can
+/// between switcher invocations. Supporting another compositor means
+/// implementing this trait and adding an arm to [`connect`].
+pub trait Backend: Send {
+    fn windows(&mut self) -> Result<Vec<BackendWindow>>;
+
+    fn focus_window(&mut self, id: u64) -> Result<()>;
+
+    /// Asks the window to close, as its close button would.
+    fn close_window(&mut self, id: u64) -> Result<()> {
+        let _ = id;
+        Err(anyhow::anyhow!("this backend cannot close windows"))
+    }
+
+    fn minimize_window(&mut self, id: u64) -> Result<()> {
+        let _ = id;
+        Err(anyhow::anyhow!("this backend cannot minimize windows"))
+    }
+
+    /// Kills the process owning the window with SIGKILL.
+    fn kill_window(&mut self, id: u64) -> Result<()> {
+        let pid = self
+            .windows()?
+            .into_iter()
+            .find(|window| window.id == id)
+            .with_context(|| format!("window {id} no longer exists"))?
+            .pid
+            .with_context(|| format!("window {id} has no known pid"))?;
+        let rc = unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+        if rc != 0 {
+            return Err(std::io::Error::last_os_error()).with_context(|| format!("kill {pid}"));
+        }
+        Ok(())
+    }
+
+    /// Logical size and integer scale of the focused output, if the
+    /// compositor reports one.
+    fn focused_output_info(&mut self) -> Result<(Option<(i32, i32)>, u32)> {
+        Ok((None, 1))
+    }
+
+    /// Workspace and output the user is on. Defaults to those of the
+    /// focused window.
+    fn focused_place(&mut self) -> Result<Place> {
+        Ok(self
+            .windows()?
+            .into_iter()
+            .find(|window| window.is_focused)
+            .map(|window| Place {
+                workspace: window.workspace,
+                output: window.output,
+            })
+            .unwrap_or_default())
+    }
+
+    /// Handle that identifies the window to
+    /// `hyprland_toplevel_export_manager_v1`, on compositors that have it.
+    fn export_handle(&self, _id: u64) -> Option<u32> {
+        None
+    }
+}
+
+pub type SharedBackend = Arc<Mutex<Box<dyn Backend>>>;
+
+/// Focus changes reported by the compositor's event stream, including the
+/// ones witcher did not cause.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowEvent {
+    Focused(u64),
+    Closed(u64),
+}
+
+/// Connects to the compositor. Backends with an event stream send
+/// [`WindowEvent`]s to `events` for as long as the connection lives.
+pub fn connect(kind: BackendKind, events: Sender<WindowEvent>) -> Result<Box<dyn Backend>> {
+    Ok(match kind {
+        BackendKind::Niri => Box::new(niri::Niri::connect(events)?),
+        BackendKind::Sway => Box::new(sway::Sway::connect()?),
+        BackendKind::Hyprland => Box::new(hyprland::Hyprland::connect(events)?),
+        BackendKind::Kwin => Box::new(kwin::Kwin::connect(events)?),
+        BackendKind::Gnome => Box::new(gnome::Gnome::connect()?),
+        BackendKind::ForeignToplevel => Box::new(foreign_toplevel::ForeignToplevel::connect()?),
+    })
+}
+
+/// Identifies the running compositor instance, so state saved for one
+/// session is not applied to another. `None` when window ids do not survive
+/// a reconnect, as with foreign-toplevel handles. KWin and GNOME export no
+/// instance variable of their own, and `WAYLAND_DISPLAY` is `wayland-0` in
+/// every session, so they are keyed by the logind session instead.
+pub fn instance_key(kind: BackendKind) -> Option<String> {
+    let var = match kind {
+        BackendKind::Niri => niri_ipc::socket::SOCKET_PATH_ENV,
+        BackendKind::Sway => "SWAYSOCK",
+        BackendKind::Hyprland => "HYPRLAND_INSTANCE_SIGNATURE",
+        BackendKind::Kwin | BackendKind::Gnome => "XDG_SESSION_ID",
+        BackendKind::ForeignToplevel => return None,
+    };
+    let value = std::env::var(var).ok().filter(|value| !value.is_empty())?;
+    Some(format!("{}:{value}", kind.name()))
+}
+
+#[derive(Clone)]
 pub struct BackendWindow {
     pub id: u64,
     pub title: Option<String>,
@@ -12,79 +128,106 @@ pub struct BackendWindow {
     pub pid: Option<i64>,
     pub process_name: Option<String>,
     pub is_focused: bool,
+    /// Compositor workspace id, where the backend reports one.
+    pub workspace: Option<u64>,
+    /// Output (connector) name, where the backend reports one.
+    pub output: Option<String>,
 }
 
-#[derive(Deserialize)]
-struct NiriWindow {
-    id: u64,
-    title: Option<String>,
-    app_id: Option<String>,
-    pid: Option<i64>,
-    is_focused: bool,
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Place {
+    pub workspace: Option<u64>,
+    pub output: Option<String>,
 }
 
-#[derive(Deserialize)]
-struct HyprClient {
-    address: Option<String>,
-    class: Option<String>,
-    #[serde(rename = "initialClass")]
-    initial_class: Option<String>,
-    focus: Option<bool>,
-    mapped: Option<bool>,
-    hidden: Option<bool>,
-    pid: Option<i64>,
-    title: Option<String>,
+/// Keeps the windows `filter` selects around `focus`. Windows the backend
+/// cannot place are kept, as are all windows when `focus` is unknown.
+pub fn filter_by_place(
+    windows: Vec<BackendWindow>,
+    filter: WindowFilter,
+    focus: &Place,
+) -> Vec<BackendWindow> {
+    windows
+        .into_iter()
+        .filter(|window| match filter {
+            WindowFilter::All => true,
+            WindowFilter::CurrentWorkspace => {
+                focus.workspace.is_none()
+                    || window.workspace.is_none()
+                    || window.workspace == focus.workspace
+            }
+            WindowFilter::CurrentOutput => {
+                focus.output.is_none() || window.output.is_none() || window.output == focus.output
+            }
+        })
+        .collect()
 }
 
-#[derive(Deserialize)]
-struct HyprMonitor {
-    focused: Option<bool>,
-    width: Option<u32>,
-    height: Option<u32>,
-    scale: Option<f64>,
+/// Window list cache for backends whose event connection only says that
+/// something changed. The list is refetched after a change was reported, or
+/// on every call once the listener is gone.
+struct WindowCache {
+    windows: Option<Vec<BackendWindow>>,
+    stale: Arc<AtomicBool>,
+    listening: Arc<AtomicBool>,
 }
 
-fn parse_hypr_address(value: &str) -> Option<u64> {
-    let trimmed = value.trim().trim_start_matches("0x");
-    u64::from_str_radix(trimmed, 16).ok()
+/// Held by an event listener thread. Dropping it marks the cache as
+/// unmonitored.
+struct CacheListener {
+    stale: Arc<AtomicBool>,
+    listening: Arc<AtomicBool>,
 }
 
-fn hyprctl(args: &[&str]) -> Result<String> {
-    let output = Command::new("hyprctl")
-        .args(args)
-        .output()
-        .context("spawn hyprctl")?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("hyprctl failed: {stderr}"));
+impl WindowCache {
+    fn new() -> Self {
+        Self {
+            windows: None,
+            stale: Arc::new(AtomicBool::new(true)),
+            listening: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn listener(&self) -> CacheListener {
+        self.listening.store(true, Ordering::SeqCst);
+        CacheListener {
+            stale: self.stale.clone(),
+            listening: self.listening.clone(),
+        }
+    }
+
+    fn get_or_fetch(
+        &mut self,
+        fetch: impl FnOnce() -> Result<Vec<BackendWindow>>,
+    ) -> Result<Vec<BackendWindow>> {
+        let stale = self.stale.swap(false, Ordering::SeqCst);
+        if stale || self.windows.is_none() || !self.listening.load(Ordering::SeqCst) {
+            self.windows = None;
+            let windows = fetch().inspect_err(|_| self.stale.store(true, Ordering::SeqCst))?;
+            self.windows = Some(windows);
+        }
+        Ok(self.windows.clone().unwrap_or_default())
     }
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
-fn hyprctl_json<T: for<'de> Deserialize<'de>>(args: &[&str]) -> Result<T> {
-    let text = hyprctl(args)?;
-    let value = serde_json::from_str(&text).context("parse hyprctl json")?;
-    Ok(value)
+impl CacheListener {
+    fn invalidate(&self) {
+        self.stale.store(true, Ordering::SeqCst);
+    }
 }
 
-fn niri_msg_json<T: for<'de> Deserialize<'de>>(command: &str) -> Result<T> {
-    let output = Command::new("niri")
-        .args(["msg", "--json", command])
-        .output()
-        .context("spawn niri msg")?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("niri msg failed: {stderr}"));
+impl Drop for CacheListener {
+    fn drop(&mut self) {
+        self.listening.store(false, Ordering::SeqCst);
     }
-    let text = String::from_utf8_lossy(&output.stdout);
-    let value = serde_json::from_str(&text).context("parse niri msg json")?;
-    Ok(value)
 }
 
-fn non_empty_app_id(app_id: Option<String>) -> Option<String> {
-    app_id.and_then(|app_id| {
-        let app_id = app_id.trim();
-        (!app_id.is_empty()).then(|| app_id.to_string())
+/// The trimmed text, or `None` when there is none, for app ids and titles
+/// that compositors report as empty strings.
+fn non_empty(text: Option<String>) -> Option<String> {
+    text.and_then(|text| {
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_string())
     })
 }
 
@@ -92,7 +235,7 @@ fn process_name(pid: Option<i64>) -> Option<String> {
     let pid = pid?;
     let comm = std::fs::read_to_string(format!("/proc/{pid}/comm"))
         .ok()
-        .and_then(|name| non_empty_app_id(Some(name)));
+        .and_then(|name| non_empty(Some(name)));
     if comm.is_some() {
         return comm;
     }
@@ -100,109 +243,48 @@ fn process_name(pid: Option<i64>) -> Option<String> {
     std::fs::read_link(format!("/proc/{pid}/exe"))
         .ok()
         .and_then(|path| path.file_name().and_then(|name| name.to_str()).map(str::to_string))
-        .and_then(|name| non_empty_app_id(Some(name)))
+        .and_then(|name| non_empty(Some(name)))
 }
 
-pub fn focus_window(backend: BackendKind, id: u64) -> Result<()> {
-    match backend {
-        BackendKind::Niri => {
-            let socket = Socket::connect().context("connect to niri socket")?;
-            let (reply, _events) = socket
-                .send(Request::Action(Action::FocusWindow { id }))
-                .context("send focus request")?;
-            match reply {
-                Ok(Response::Handled) => Ok(()),
-                Ok(_) => Ok(()),
-                Err(message) => Err(anyhow::anyhow!(message)),
-            }
-        }
-        BackendKind::Hyprland => {
-            let addr = format!("address:0x{id:x}");
-            hyprctl(&["dispatch", "focuswindow", &addr])?;
-            Ok(())
-        }
-        _ => Err(anyhow::anyhow!("backend not supported")),
-    }
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub fn focused_output_info(backend: BackendKind) -> Result<(Option<(i32, i32)>, u32)> {
-    match backend {
-        BackendKind::Niri => {
-            let socket = Socket::connect().context("connect to niri socket")?;
-            let (reply, _events) =
-                socket.send(Request::FocusedOutput).context("send focused output request")?;
-            let output = match reply {
-                Ok(Response::FocusedOutput(output)) => output,
-                Ok(_) => None,
-                Err(message) => return Err(anyhow::anyhow!(message)),
-            };
-            if let Some(output) = output {
-                if let Some(logical) = output.logical {
-                    let scale = logical.scale.max(1.0).round() as u32;
-                    return Ok((Some((logical.width as i32, logical.height as i32)), scale.max(1)));
-                }
-            }
-            Ok((None, 1))
-        }
-        BackendKind::Hyprland => {
-            let output = hyprctl_json::<Vec<HyprMonitor>>(&["-j", "monitors"])?;
-            if let Some(monitor) = output.into_iter().find(|m| m.focused.unwrap_or(false)) {
-                let scale = monitor.scale.unwrap_or(1.0).max(1.0);
-                return Ok((
-                    Some((monitor.width.unwrap_or(0) as i32, monitor.height.unwrap_or(0) as i32)),
-                    scale.round() as u32,
-                ));
-            }
-            Ok((None, 1))
-        }
-        _ => Ok((None, 1)),
-    }
-}
+    #[test]
+    fn place_filters_keep_windows_they_cannot_place() {
+        let window = |id: u64, workspace: Option<u64>, output: Option<&str>| BackendWindow {
+            id,
+            title: None,
+            app_id: None,
+            pid: None,
+            process_name: None,
+            is_focused: false,
+            workspace,
+            output: output.map(str::to_string),
+        };
+        let windows = vec![
+            window(1, Some(1), Some("DP-1")),
+            window(2, Some(2), Some("DP-1")),
+            window(3, Some(3), Some("HDMI-A-1")),
+            window(4, None, None),
+        ];
+        let ids = |filter, focus: &Place| {
+            filter_by_place(windows.clone(), filter, focus)
+                .iter()
+                .map(|window| window.id)
+                .collect::<Vec<_>>()
+        };
+        let focus = Place {
+            workspace: Some(2),
+            output: Some("DP-1".to_string()),
+        };
 
-pub fn backend_windows(backend: BackendKind) -> Result<Vec<BackendWindow>> {
-    match backend {
-        BackendKind::Niri => {
-            let windows = niri_msg_json::<Vec<NiriWindow>>("windows")?;
-            Ok(windows
-                .into_iter()
-                .map(|window| BackendWindow {
-                    id: window.id,
-                    title: non_empty_app_id(window.title),
-                    app_id: non_empty_app_id(window.app_id),
-                    pid: window.pid,
-                    process_name: process_name(window.pid),
-                    is_focused: window.is_focused,
-                })
-                .collect())
-        }
-        BackendKind::Hyprland => {
-            let clients = hyprctl_json::<Vec<HyprClient>>(&["-j", "clients"])?;
-            let mut windows = Vec::new();
-            for client in clients {
-                if client.mapped == Some(false) || client.hidden == Some(true) {
-                    continue;
-                }
-                let addr = match client.address.as_deref() {
-                    Some(addr) => addr,
-                    None => continue,
-                };
-                let id = match parse_hypr_address(addr) {
-                    Some(id) => id,
-                    None => continue,
-                };
-                let app_id = non_empty_app_id(client.initial_class.clone())
-                    .or_else(|| non_empty_app_id(client.class.clone()));
-                windows.push(BackendWindow {
-                    id,
-                    title: non_empty_app_id(client.title.clone()),
-                    app_id,
-                    pid: client.pid,
-                    process_name: process_name(client.pid),
-                    is_focused: client.focus.unwrap_or(false),
-                });
-            }
-            Ok(windows)
-        }
-        _ => Err(anyhow::anyhow!("backend not supported")),
+        assert_eq!(ids(WindowFilter::All, &focus), vec![1, 2, 3, 4]);
+        assert_eq!(ids(WindowFilter::CurrentWorkspace, &focus), vec![2, 4]);
+        assert_eq!(ids(WindowFilter::CurrentOutput, &focus), vec![1, 2, 4]);
+        assert_eq!(
+            ids(WindowFilter::CurrentOutput, &Place::default()),
+            vec![1, 2, 3, 4]
+        );
     }
 }
@@ -0,0 +1,367 @@
+use anyhow::{Context, Result};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+    backend::ObjectId,
+    delegate_noop, event_created_child,
+    globals::{GlobalListContents, registry_queue_init},
+    protocol::{wl_registry, wl_seat},
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+use super::{Backend, BackendWindow, non_empty};
+
+/// Toplevel handle ids are only meaningful while their Wayland connection is
+/// alive, so the connection lives as long as the backend.
+pub struct ForeignToplevel {
+    conn: Connection,
+    event_queue: EventQueue<ToplevelState>,
+    state: ToplevelState,
+}
+
+#[derive(Default)]
+struct ToplevelState {
+    seat: Option<wl_seat::WlSeat>,
+    toplevels: Vec<Toplevel>,
+    /// Last id given to a toplevel.
+    last_id: u64,
+    finished: bool,
+}
+
+struct Toplevel {
+    /// Never reused, unlike the handle's protocol id, so the MRU order of a
+    /// closed window cannot carry over to a new one.
+    id: u64,
+    handle: ToplevelHandle,
+    title: Option<String>,
+    app_id: Option<String>,
+    activated: bool,
+}
+
+enum ToplevelHandle {
+    Wlr(ZwlrForeignToplevelHandleV1),
+    Ext(ExtForeignToplevelHandleV1),
+}
+
+impl ToplevelHandle {
+    fn object_id(&self) -> ObjectId {
+        match self {
+            ToplevelHandle::Wlr(handle) => handle.id(),
+            ToplevelHandle::Ext(handle) => handle.id(),
+        }
+    }
+}
+
+impl Toplevel {
+    fn apply_wlr(&mut self, event: zwlr_foreign_toplevel_handle_v1::Event) {
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => self.title = Some(title),
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => self.app_id = Some(app_id),
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                let activated = zwlr_foreign_toplevel_handle_v1::State::Activated as u32;
+                self.activated = state
+                    .chunks_exact(4)
+                    .map(|value| u32::from_ne_bytes(value.try_into().unwrap()))
+                    .any(|value| value == activated);
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_ext(&mut self, event: ext_foreign_toplevel_handle_v1::Event) {
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => self.title = Some(title),
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => self.app_id = Some(app_id),
+            _ => {}
+        }
+    }
+
+    /// Neither protocol tells which process a toplevel belongs to, so the
+    /// pid and process name stay unknown.
+    fn backend_window(&self) -> BackendWindow {
+        BackendWindow {
+            id: self.id,
+            title: non_empty(self.title.clone()),
+            app_id: non_empty(self.app_id.clone()),
+            pid: None,
+            process_name: None,
+            is_focused: self.activated,
+            workspace: None,
+            output: None,
+        }
+    }
+}
+
+impl ToplevelState {
+    fn add(&mut self, handle: ToplevelHandle) {
+        self.last_id += 1;
+        self.toplevels.push(Toplevel {
+            id: self.last_id,
+            handle,
+            title: None,
+            app_id: None,
+            activated: false,
+        });
+    }
+
+    fn toplevel_mut(&mut self, id: u64) -> Option<&mut Toplevel> {
+        self.toplevels.iter_mut().find(|toplevel| toplevel.id == id)
+    }
+
+    fn by_handle_mut(&mut self, handle: &ObjectId) -> Option<&mut Toplevel> {
+        self.toplevels
+            .iter_mut()
+            .find(|toplevel| toplevel.handle.object_id() == *handle)
+    }
+
+    fn remove(&mut self, handle: &ObjectId) {
+        self.toplevels
+            .retain(|toplevel| toplevel.handle.object_id() != *handle);
+    }
+}
+
+impl ForeignToplevel {
+    pub fn connect() -> Result<Self> {
+        let conn = Connection::connect_to_env().context("connect to Wayland")?;
+        let (globals, mut event_queue) =
+            registry_queue_init::<ToplevelState>(&conn).context("init registry")?;
+        let qh = event_queue.handle();
+
+        let mut state = ToplevelState {
+            seat: globals.bind::<wl_seat::WlSeat, _, _>(&qh, 1..=7, ()).ok(),
+            ..ToplevelState::default()
+        };
+        if globals
+            .bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ())
+            .is_err()
+        {
+            globals
+                .bind::<ExtForeignToplevelListV1, _, _>(&qh, 1..=1, ())
+                .context(
+                    "neither zwlr_foreign_toplevel_manager_v1 nor ext_foreign_toplevel_list_v1 \
+                     is available",
+                )?;
+        }
+        event_queue
+            .roundtrip(&mut state)
+            .context("fetch initial toplevels")?;
+
+        Ok(Self {
+            conn,
+            event_queue,
+            state,
+        })
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.event_queue
+            .roundtrip(&mut self.state)
+            .context("sync foreign toplevels")?;
+        if self.state.finished {
+            return Err(anyhow::anyhow!("compositor stopped sending toplevel updates"));
+        }
+        Ok(())
+    }
+
+    /// Sends a request on the wlr handle of toplevel `id`; `what` names it
+    /// in the error for ext-only toplevels.
+    fn with_wlr_handle(
+        &mut self,
+        id: u64,
+        what: &str,
+        request: impl FnOnce(&ZwlrForeignToplevelHandleV1),
+    ) -> Result<()> {
+        let toplevel = self
+            .state
+            .toplevel_mut(id)
+            .with_context(|| format!("toplevel {id} no longer exists"))?;
+        match &toplevel.handle {
+            ToplevelHandle::Wlr(handle) => request(handle),
+            ToplevelHandle::Ext(_) => {
+                return Err(anyhow::anyhow!(
+                    "ext_foreign_toplevel_list_v1 cannot {what} windows"
+                ));
+            }
+        }
+        self.conn.flush().context("flush wayland")?;
+        Ok(())
+    }
+}
+
+impl Backend for ForeignToplevel {
+    fn windows(&mut self) -> Result<Vec<BackendWindow>> {
+        self.sync()?;
+        Ok(self
+            .state
+            .toplevels
+            .iter()
+            .map(Toplevel::backend_window)
+            .collect())
+    }
+
+    fn focus_window(&mut self, id: u64) -> Result<()> {
+        let seat = self.state.seat.clone().context("no wl_seat available")?;
+        self.with_wlr_handle(id, "activate", |handle| handle.activate(&seat))
+    }
+
+    fn close_window(&mut self, id: u64) -> Result<()> {
+        self.with_wlr_handle(id, "close", |handle| handle.close())
+    }
+
+    fn minimize_window(&mut self, id: u64) -> Result<()> {
+        self.with_wlr_handle(id, "minimize", |handle| handle.set_minimized())
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for ToplevelState {
+    fn event(
+        _state: &mut Self,
+        _registry: &wl_registry::WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        _manager: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                state.add(ToplevelHandle::Wlr(toplevel));
+            }
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => state.finished = true,
+            _ => {}
+        }
+    }
+
+    event_created_child!(ToplevelState, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+            state.remove(&handle.id());
+            handle.destroy();
+            return;
+        }
+        if let Some(toplevel) = state.by_handle_mut(&handle.id()) {
+            toplevel.apply_wlr(event);
+        }
+    }
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        _list: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } => {
+                state.add(ToplevelHandle::Ext(toplevel));
+            }
+            ext_foreign_toplevel_list_v1::Event::Finished => state.finished = true,
+            _ => {}
+        }
+    }
+
+    event_created_child!(ToplevelState, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for ToplevelState {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_handle_v1::Event::Closed = event {
+            state.remove(&handle.id());
+            handle.destroy();
+            return;
+        }
+        if let Some(toplevel) = state.by_handle_mut(&handle.id()) {
+            toplevel.apply_ext(event);
+        }
+    }
+}
+
+delegate_noop!(ToplevelState: ignore wl_seat::WlSeat);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use wayland_client::backend::Backend;
+
+    #[test]
+    fn handles_map_to_windows_with_fresh_ids() {
+        // Inert handles need a connection, though nothing is sent on it.
+        let (socket, _server) = UnixStream::pair().unwrap();
+        let conn = Connection::from_backend(Backend::connect(socket).unwrap());
+        let handle = || {
+            ToplevelHandle::Wlr(ZwlrForeignToplevelHandleV1::inert(
+                conn.backend().downgrade(),
+            ))
+        };
+
+        let mut state = ToplevelState::default();
+        state.add(handle());
+        let toplevel = &mut state.toplevels[0];
+        toplevel.apply_wlr(zwlr_foreign_toplevel_handle_v1::Event::Title {
+            title: "~/src".to_string(),
+        });
+        toplevel.apply_wlr(zwlr_foreign_toplevel_handle_v1::Event::AppId {
+            app_id: String::new(),
+        });
+        let activated = zwlr_foreign_toplevel_handle_v1::State::Activated as u32;
+        toplevel.apply_wlr(zwlr_foreign_toplevel_handle_v1::Event::State {
+            state: [2u32, activated]
+                .iter()
+                .flat_map(|value| value.to_ne_bytes())
+                .collect(),
+        });
+        let window = toplevel.backend_window();
+        assert_eq!(window.id, 1);
+        assert_eq!(window.title.as_deref(), Some("~/src"));
+        assert_eq!(window.app_id, None);
+        assert!(window.is_focused);
+
+        // A handle that replaces a closed one gets a new id.
+        state.toplevels.clear();
+        state.add(handle());
+        assert_eq!(state.toplevels[0].backend_window().id, 2);
+        assert!(!state.toplevels[0].backend_window().is_focused);
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use zbus::blocking::{Connection, connection::Builder};
+
+use super::{Backend, BackendWindow, non_empty, process_name};
+
+const BUS_NAME: &str = "io.github.iondodon.Witcher.Shell";
+const OBJECT_PATH: &str = "/io/github/iondodon/Witcher/Shell";
+const INTERFACE: &str = "io.github.iondodon.Witcher.Shell";
+
+/// Client for the companion Shell extension in `gnome-extension/`.
+pub struct Gnome {
+    conn: Connection,
+}
+
+#[derive(Deserialize)]
+struct GnomeWindow {
+    id: u64,
+    wm_class: Option<String>,
+    title: Option<String>,
+    pid: Option<i64>,
+    #[serde(default)]
+    focused: bool,
+}
+
+impl Gnome {
+    pub fn connect() -> Result<Self> {
+        Self::connect_with(Builder::session().context("connect to session bus")?)
+    }
+
+    fn connect_with(builder: Builder<'_>) -> Result<Self> {
+        let conn = builder.build().context("connect to session bus")?;
+        Ok(Self { conn })
+    }
+
+    fn call<B, R>(&self, method: &str, body: &B) -> Result<R>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+        R: for<'de> Deserialize<'de> + zbus::zvariant::Type,
+    {
+        let reply = self
+            .conn
+            .call_method(Some(BUS_NAME), OBJECT_PATH, Some(INTERFACE), method, body)
+            .with_context(|| {
+                format!("call {INTERFACE}.{method} (is the witcher GNOME extension enabled?)")
+            })?;
+        reply
+            .body()
+            .deserialize::<R>()
+            .with_context(|| format!("parse {method} reply"))
+    }
+
+    /// Calls a method that takes a window id and reports whether the window
+    /// was found.
+    fn window_call(&self, method: &str, id: u64) -> Result<()> {
+        if self.call::<_, bool>(method, &(id,))? {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("GNOME window {id} no longer exists"))
+        }
+    }
+}
+
+impl Backend for Gnome {
+    fn windows(&mut self) -> Result<Vec<BackendWindow>> {
+        let text = self.call::<_, String>("ListWindows", &())?;
+        let windows = serde_json::from_str::<Vec<GnomeWindow>>(&text)
+            .context("parse GNOME window list json")?;
+        Ok(windows
+            .into_iter()
+            .map(|window| BackendWindow {
+                id: window.id,
+                title: non_empty(window.title),
+                app_id: non_empty(window.wm_class),
+                pid: window.pid,
+                process_name: process_name(window.pid),
+                is_focused: window.focused,
+                workspace: None,
+                output: None,
+            })
+            .collect())
+    }
+
+    fn focus_window(&mut self, id: u64) -> Result<()> {
+        self.window_call("ActivateWindow", id)
+    }
+
+    fn close_window(&mut self, id: u64) -> Result<()> {
+        self.window_call("CloseWindow", id)
+    }
+
+    fn minimize_window(&mut self, id: u64) -> Result<()> {
+        self.window_call("MinimizeWindow", id)
+    }
+
+    fn focused_output_info(&mut self) -> Result<(Option<(i32, i32)>, u32)> {
+        let (width, height, scale) = self.call::<_, (i32, i32, f64)>("FocusedMonitor", &())?;
+        if width <= 0 || height <= 0 {
+            return Ok((None, 1));
+        }
+        let scale = scale.max(1.0).round() as u32;
+        Ok((Some((width, height)), scale.max(1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_bus::PrivateBus;
+    use std::sync::{Arc, Mutex};
+
+    struct MockShell {
+        activated: Arc<Mutex<Vec<u64>>>,
+    }
+
+    #[zbus::interface(name = "io.github.iondodon.Witcher.Shell")]
+    impl MockShell {
+        fn list_windows(&self) -> String {
+            r#"[
+                {"id": 7, "wm_class": "org.gnome.Ptyxis", "title": "~", "pid": 3100,
+                 "focused": true},
+                {"id": 12, "wm_class": "firefox", "title": "Mozilla Firefox", "pid": null,
+                 "focused": false},
+                {"id": 13, "wm_class": " ", "title": "", "pid": null, "focused": false}
+            ]"#
+            .to_string()
+        }
+
+        fn activate_window(&self, id: u64) -> bool {
+            self.activated.lock().unwrap().push(id);
+            id != 404
+        }
+
+        #[zbus(out_args("width", "height", "scale"))]
+        fn focused_monitor(&self) -> (i32, i32, f64) {
+            (1440, 900, 2.0)
+        }
+    }
+
+    #[test]
+    fn client_reads_windows_and_activates_through_extension() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let activated = Arc::new(Mutex::new(Vec::new()));
+        let _mock_shell = bus
+            .builder()
+            .name(BUS_NAME)
+            .unwrap()
+            .serve_at(
+                OBJECT_PATH,
+                MockShell {
+                    activated: activated.clone(),
+                },
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let mut gnome = Gnome::connect_with(bus.builder()).expect("connect");
+        let windows = gnome.windows().expect("windows");
+
+        assert_eq!(
+            windows.iter().map(|window| window.id).collect::<Vec<_>>(),
+            vec![7, 12, 13]
+        );
+        assert_eq!(windows[0].app_id.as_deref(), Some("org.gnome.Ptyxis"));
+        assert!(windows[0].is_focused);
+        assert_eq!(windows[1].title.as_deref(), Some("Mozilla Firefox"));
+        assert_eq!(windows[2].app_id, None);
+        assert_eq!(windows[2].title, None);
+
+        gnome.focus_window(12).expect("focus");
+        assert!(gnome.focus_window(404).is_err());
+        assert_eq!(*activated.lock().unwrap(), vec![12, 404]);
+
+        assert_eq!(
+            gnome.focused_output_info().expect("monitor"),
+            (Some((1440, 900)), 2)
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::mpsc::Sender,
+    thread,
+};
+
+use super::{Backend, BackendWindow, Place, WindowCache, WindowEvent, non_empty, process_name};
+
+/// Hyprland closes `.socket.sock` after every reply, so requests go straight
+/// to the socket instead of through `hyprctl`. The long-lived `.socket2.sock`
+/// event connection tells us when the cached window list is out of date.
+pub struct Hyprland {
+    socket: HyprSocket,
+    cache: WindowCache,
+}
+
+struct HyprSocket {
+    dir: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct HyprClient {
+    address: Option<String>,
+    class: Option<String>,
+    #[serde(rename = "initialClass")]
+    initial_class: Option<String>,
+    focus: Option<bool>,
+    mapped: Option<bool>,
+    hidden: Option<bool>,
+    pid: Option<i64>,
+    title: Option<String>,
+    workspace: Option<HyprWorkspaceRef>,
+    /// Monitor id.
+    monitor: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct HyprWorkspaceRef {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct HyprMonitor {
+    id: Option<i64>,
+    name: Option<String>,
+    #[serde(rename = "activeWorkspace")]
+    active_workspace: Option<HyprWorkspaceRef>,
+    focused: Option<bool>,
+    width: Option<u32>,
+    height: Option<u32>,
+    scale: Option<f64>,
+}
+
+fn parse_hypr_address(value: &str) -> Option<u64> {
+    let trimmed = value.trim().trim_start_matches("0x");
+    u64::from_str_radix(trimmed, 16).ok()
+}
+
+/// Maps a `.socket2.sock` line to a focus or close event. `activewindowv2`
+/// carries an empty address when focus leaves all windows.
+fn parse_event(line: &str) -> Option<WindowEvent> {
+    let (name, data) = line.split_once(">>")?;
+    match name {
+        "activewindowv2" => parse_hypr_address(data).map(WindowEvent::Focused),
+        "closewindow" => parse_hypr_address(data).map(WindowEvent::Closed),
+        _ => None,
+    }
+}
+
+fn socket_dir() -> Result<PathBuf> {
+    let signature = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").context(
+        "HYPRLAND_INSTANCE_SIGNATURE is not set, are you running this within Hyprland?",
+    )?;
+    let mut candidates = Vec::new();
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        candidates.push(PathBuf::from(runtime_dir).join("hypr").join(&signature));
+    }
+    candidates.push(PathBuf::from("/tmp/hypr").join(&signature));
+    candidates
+        .into_iter()
+        .find(|dir| dir.join(".socket.sock").exists())
+        .context("Hyprland socket not found")
+}
+
+impl Hyprland {
+    pub fn connect(events: Sender<WindowEvent>) -> Result<Self> {
+        let socket_dir = socket_dir()?;
+        let cache = WindowCache::new();
+        let stream = UnixStream::connect(socket_dir.join(".socket2.sock"))
+            .context("connect to Hyprland event socket")?;
+        let listener = cache.listener();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                listener.invalidate();
+                if let Some(event) = parse_event(&line) {
+                    let _ = events.send(event);
+                }
+            }
+            eprintln!("witcher: Hyprland event socket closed");
+        });
+        Ok(Self {
+            socket: HyprSocket { dir: socket_dir },
+            cache,
+        })
+    }
+
+    fn dispatch(&self, dispatcher: &str) -> Result<()> {
+        let reply = self.socket.request(&format!("dispatch {dispatcher}"))?;
+        if reply.trim() != "ok" {
+            let name = dispatcher.split_whitespace().next().unwrap_or(dispatcher);
+            return Err(anyhow::anyhow!("Hyprland {name} failed: {}", reply.trim()));
+        }
+        Ok(())
+    }
+}
+
+impl HyprSocket {
+    fn request(&self, command: &str) -> Result<String> {
+        let mut stream = UnixStream::connect(self.dir.join(".socket.sock"))
+            .context("connect to Hyprland socket")?;
+        stream
+            .write_all(command.as_bytes())
+            .context("send Hyprland request")?;
+        let mut reply = String::new();
+        stream
+            .read_to_string(&mut reply)
+            .context("read Hyprland reply")?;
+        Ok(reply)
+    }
+
+    fn request_json<T: for<'de> Deserialize<'de>>(&self, command: &str) -> Result<T> {
+        let text = self.request(&format!("j/{command}"))?;
+        let value = serde_json::from_str(&text).context("parse Hyprland json")?;
+        Ok(value)
+    }
+
+    fn fetch_windows(&self) -> Result<Vec<BackendWindow>> {
+        let clients = self.request_json::<Vec<HyprClient>>("clients")?;
+        let monitors = self
+            .request_json::<Vec<HyprMonitor>>("monitors")
+            .unwrap_or_default();
+        let mut windows = Vec::new();
+        for client in clients {
+            if client.mapped == Some(false) || client.hidden == Some(true) {
+                continue;
+            }
+            let addr = match client.address.as_deref() {
+                Some(addr) => addr,
+                None => continue,
+            };
+            let id = match parse_hypr_address(addr) {
+                Some(id) => id,
+                None => continue,
+            };
+            let app_id =
+                non_empty(client.initial_class.clone()).or_else(|| non_empty(client.class.clone()));
+            windows.push(BackendWindow {
+                id,
+                title: non_empty(client.title.clone()),
+                app_id,
+                pid: client.pid,
+                process_name: process_name(client.pid),
+                is_focused: client.focus.unwrap_or(false),
+                // Special workspaces have negative ids; the cast keeps them
+                // distinct.
+                workspace: client.workspace.map(|workspace| workspace.id as u64),
+                output: monitors
+                    .iter()
+                    .find(|monitor| monitor.id.is_some() && monitor.id == client.monitor)
+                    .and_then(|monitor| monitor.name.clone()),
+            });
+        }
+        Ok(windows)
+    }
+}
+
+impl Backend for Hyprland {
+    fn windows(&mut self) -> Result<Vec<BackendWindow>> {
+        let socket = &self.socket;
+        self.cache.get_or_fetch(|| socket.fetch_windows())
+    }
+
+    fn focus_window(&mut self, id: u64) -> Result<()> {
+        self.dispatch(&format!("focuswindow address:0x{id:x}"))
+    }
+
+    fn close_window(&mut self, id: u64) -> Result<()> {
+        self.dispatch(&format!("closewindow address:0x{id:x}"))
+    }
+
+    fn minimize_window(&mut self, id: u64) -> Result<()> {
+        // Hyprland has no minimized state; the special workspace is the
+        // usual stand-in.
+        self.dispatch(&format!(
+            "movetoworkspacesilent special:minimized,address:0x{id:x}"
+        ))
+    }
+
+    fn focused_output_info(&mut self) -> Result<(Option<(i32, i32)>, u32)> {
+        let output = self.socket.request_json::<Vec<HyprMonitor>>("monitors")?;
+        if let Some(monitor) = output.into_iter().find(|m| m.focused.unwrap_or(false)) {
+            let scale = monitor.scale.unwrap_or(1.0).max(1.0);
+            return Ok((
+                Some((monitor.width.unwrap_or(0) as i32, monitor.height.unwrap_or(0) as i32)),
+                scale.round() as u32,
+            ));
+        }
+        Ok((None, 1))
+    }
+
+    fn focused_place(&mut self) -> Result<Place> {
+        let monitors = self.socket.request_json::<Vec<HyprMonitor>>("monitors")?;
+        Ok(monitors
+            .into_iter()
+            .find(|monitor| monitor.focused.unwrap_or(false))
+            .map(|monitor| Place {
+                workspace: monitor.active_workspace.map(|workspace| workspace.id as u64),
+                output: monitor.name,
+            })
+            .unwrap_or_default())
+    }
+
+    fn export_handle(&self, id: u64) -> Option<u32> {
+        // The protocol takes the lower 32 bits of the window address.
+        Some(id as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_lines_map_to_focus_and_close() {
+        let events = [
+            "activewindow>>foot,~",
+            "activewindowv2>>55d0c4e0c2a0",
+            "activewindowv2>>",
+            "openwindow>>55d0c4e0c2b0,1,foot,~",
+            "closewindow>>55d0c4e0c2a0",
+        ]
+        .into_iter()
+        .filter_map(parse_event)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                WindowEvent::Focused(0x55d0c4e0c2a0),
+                WindowEvent::Closed(0x55d0c4e0c2a0),
+            ]
+        );
+    }
+}
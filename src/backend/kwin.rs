@@ -0,0 +1,393 @@
+use anyhow::{Context, Result};
+use async_channel::Receiver;
+use async_io::Timer;
+use serde::Deserialize;
+use std::{
+    future::{Future, poll_fn},
+    pin::pin,
+    sync::{Arc, Mutex, mpsc::Sender},
+    task::Poll,
+    thread,
+    time::{Duration, Instant},
+};
+use zbus::blocking::{Connection, connection::Builder};
+
+use super::{Backend, BackendWindow, WindowEvent, non_empty, process_name};
+use crate::daemon::runtime_socket_path;
+
+const BUS_NAME: &str = "io.github.iondodon.Witcher.KWin";
+const OBJECT_PATH: &str = "/io/github/iondodon/Witcher/KWin";
+const SCRIPT_NAME: &str = "witcher";
+const SCRIPT_SOURCE: &str = include_str!("kwin/witcher.js");
+const INITIAL_WINDOWS_TIMEOUT: Duration = Duration::from_secs(1);
+/// Below the 25 second timeout KWin gives the script's D-Bus calls.
+const ACTION_POLL_TIMEOUT: Duration = Duration::from_secs(20);
+/// Window actions waiting for the script; more mean it has stopped asking.
+const ACTION_QUEUE: usize = 8;
+
+pub struct Kwin {
+    conn: Connection,
+    state: Arc<Mutex<KwinState>>,
+    actions: async_channel::Sender<String>,
+}
+
+#[derive(Default)]
+struct KwinState {
+    windows: Vec<KwinWindow>,
+    received: bool,
+}
+
+#[derive(Clone, Deserialize)]
+struct KwinWindow {
+    id: String,
+    #[serde(rename = "resourceClass")]
+    resource_class: Option<String>,
+    caption: Option<String>,
+    pid: Option<i64>,
+    #[serde(default)]
+    active: bool,
+}
+
+/// Receives the window list that the bundled KWin script pushes with
+/// `callDBus`, and hands it the window actions to run.
+struct KwinBridge {
+    state: Arc<Mutex<KwinState>>,
+    actions: Receiver<String>,
+    events: Sender<WindowEvent>,
+}
+
+#[zbus::interface(name = "io.github.iondodon.Witcher.KWin")]
+impl KwinBridge {
+    fn update_windows(&self, windows: String) -> zbus::fdo::Result<()> {
+        let windows = serde_json::from_str::<Vec<KwinWindow>>(&windows)
+            .map_err(|err| zbus::fdo::Error::InvalidArgs(err.to_string()))?;
+        let mut state = self.state.lock().unwrap();
+        state.windows = windows;
+        state.received = true;
+        Ok(())
+    }
+
+    fn window_activated(&self, id: String) {
+        let mut state = self.state.lock().unwrap();
+        for window in &mut state.windows {
+            window.active = window.id == id;
+        }
+        let _ = self.events.send(WindowEvent::Focused(window_id(&id)));
+    }
+
+    /// Long-polled by the script: the next window action as JSON, or an
+    /// empty string once `ACTION_POLL_TIMEOUT` passes without one.
+    async fn next_action(&self) -> String {
+        let mut action = pin!(self.actions.recv());
+        let mut timeout = pin!(Timer::after(ACTION_POLL_TIMEOUT));
+        poll_fn(|cx| {
+            if let Poll::Ready(action) = action.as_mut().poll(cx) {
+                return Poll::Ready(action.unwrap_or_default());
+            }
+            timeout.as_mut().poll(cx).map(|_| String::new())
+        })
+        .await
+    }
+}
+
+impl Kwin {
+    pub fn connect(events: Sender<WindowEvent>) -> Result<Self> {
+        Self::connect_with(
+            Builder::session().context("connect to session bus")?,
+            events,
+        )
+    }
+
+    fn connect_with(builder: Builder<'_>, events: Sender<WindowEvent>) -> Result<Self> {
+        let state = Arc::new(Mutex::new(KwinState::default()));
+        let (actions, receiver) = async_channel::bounded(ACTION_QUEUE);
+        let conn = builder
+            .name(BUS_NAME)
+            .context("request KWin bridge bus name")?
+            .serve_at(
+                OBJECT_PATH,
+                KwinBridge {
+                    state: state.clone(),
+                    actions: receiver,
+                    events,
+                },
+            )
+            .context("serve KWin bridge")?
+            .build()
+            .context("start KWin bridge")?;
+        let kwin = Self {
+            conn,
+            state,
+            actions,
+        };
+        kwin.load_script()?;
+        kwin.wait_for_windows();
+        Ok(kwin)
+    }
+
+    fn wait_for_windows(&self) {
+        let deadline = Instant::now() + INITIAL_WINDOWS_TIMEOUT;
+        while !self.state.lock().unwrap().received && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Loads the bundled script, replacing the one an earlier daemon left.
+    fn load_script(&self) -> Result<()> {
+        let path = runtime_socket_path(&format!("witcher-kwin-{SCRIPT_NAME}.js"))?;
+        std::fs::write(&path, SCRIPT_SOURCE)
+            .with_context(|| format!("write {}", path.display()))?;
+
+        let _ = self.call_scripting("unloadScript", &(SCRIPT_NAME,));
+        let script_id = self
+            .call_scripting(
+                "loadScript",
+                &(path.to_string_lossy().as_ref(), SCRIPT_NAME),
+            )?
+            .body()
+            .deserialize::<i32>()
+            .context("parse loadScript reply")?;
+        if script_id < 0 {
+            return Err(anyhow::anyhow!("KWin refused to load {}", path.display()));
+        }
+
+        // KWin 6 exposes loaded scripts under /Scripting, KWin 5 at the root.
+        let run = |path: String| {
+            self.conn.call_method(
+                Some("org.kde.KWin"),
+                path.as_str(),
+                Some("org.kde.kwin.Script"),
+                "run",
+                &(),
+            )
+        };
+        run(format!("/Scripting/Script{script_id}"))
+            .or_else(|_| run(format!("/{script_id}")))
+            .context("run KWin script")?;
+        Ok(())
+    }
+
+    fn window_action(&self, id: u64, action: &str) -> Result<()> {
+        let uuid = self
+            .state
+            .lock()
+            .unwrap()
+            .windows
+            .iter()
+            .find(|window| window_id(&window.id) == id)
+            .map(|window| window.id.clone())
+            .with_context(|| format!("KWin window {id} no longer exists"))?;
+        let request = serde_json::json!({ "id": uuid, "action": action }).to_string();
+        self.actions
+            .try_send(request)
+            .map_err(|_| anyhow::anyhow!("the KWin script is not taking window actions"))
+    }
+
+    fn call_scripting<B>(&self, method: &str, body: &B) -> Result<zbus::Message>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        self.conn
+            .call_method(
+                Some("org.kde.KWin"),
+                "/Scripting",
+                Some("org.kde.kwin.Scripting"),
+                method,
+                body,
+            )
+            .with_context(|| format!("call org.kde.kwin.Scripting.{method}"))
+    }
+}
+
+impl Backend for Kwin {
+    fn windows(&mut self) -> Result<Vec<BackendWindow>> {
+        let state = self.state.lock().unwrap();
+        if !state.received {
+            return Err(anyhow::anyhow!("KWin script has not reported any windows"));
+        }
+        Ok(state
+            .windows
+            .iter()
+            .map(|window| BackendWindow {
+                id: window_id(&window.id),
+                title: non_empty(window.caption.clone()),
+                app_id: non_empty(window.resource_class.clone()),
+                pid: window.pid,
+                process_name: process_name(window.pid),
+                is_focused: window.active,
+                workspace: None,
+                output: None,
+            })
+            .collect())
+    }
+
+    fn focus_window(&mut self, id: u64) -> Result<()> {
+        self.window_action(id, "activate")
+    }
+
+    fn close_window(&mut self, id: u64) -> Result<()> {
+        self.window_action(id, "close")
+    }
+
+    fn minimize_window(&mut self, id: u64) -> Result<()> {
+        self.window_action(id, "minimize")
+    }
+}
+
+/// KWin identifies windows by UUID; hash it into the numeric id the rest of
+/// witcher uses. FNV-1a keeps the id stable across daemon restarts.
+fn window_id(uuid: &str) -> u64 {
+    uuid.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_bus::PrivateBus;
+
+    const WINDOWS_JSON: &str = r#"[
+        {"id": "{0b6f6a4e-1111-4c3b-9a55-000000000001}", "resourceClass": "org.kde.konsole",
+         "caption": "~ : zsh", "pid": 4242, "active": true},
+        {"id": "{0b6f6a4e-1111-4c3b-9a55-000000000002}", "resourceClass": "firefox",
+         "caption": "Mozilla Firefox", "pid": null, "active": false}
+    ]"#;
+
+    /// Stands in for KWin: records loaded scripts and, when the witcher
+    /// script runs, pushes a window list back the way the real script does.
+    struct MockScripting {
+        address: String,
+        loaded: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    #[zbus::interface(name = "org.kde.kwin.Scripting")]
+    impl MockScripting {
+        #[zbus(name = "loadScript")]
+        fn load_script(&self, path: String, name: String) -> i32 {
+            let source = std::fs::read_to_string(&path).unwrap_or_default();
+            let mut loaded = self.loaded.lock().unwrap();
+            loaded.push((name.clone(), source));
+            if name == SCRIPT_NAME {
+                let address = self.address.clone();
+                thread::spawn(move || {
+                    let conn = Builder::address(address.as_str()).unwrap().build().unwrap();
+                    conn.call_method(
+                        Some(BUS_NAME),
+                        OBJECT_PATH,
+                        Some(BUS_NAME),
+                        "UpdateWindows",
+                        &(WINDOWS_JSON,),
+                    )
+                    .unwrap();
+                });
+            }
+            loaded.len() as i32 - 1
+        }
+
+        #[zbus(name = "unloadScript")]
+        fn unload_script(&self, _name: String) -> bool {
+            true
+        }
+    }
+
+    struct MockScript;
+
+    #[zbus::interface(name = "org.kde.kwin.Script")]
+    impl MockScript {
+        #[zbus(name = "run")]
+        fn run(&self) {}
+    }
+
+    #[test]
+    fn bridge_reports_windows_and_activates_through_script() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let loaded = Arc::new(Mutex::new(Vec::new()));
+        let _mock_kwin = bus
+            .builder()
+            .name("org.kde.KWin")
+            .unwrap()
+            .serve_at(
+                "/Scripting",
+                MockScripting {
+                    address: bus.address.clone(),
+                    loaded: loaded.clone(),
+                },
+            )
+            .unwrap()
+            .serve_at("/Scripting/Script0", MockScript)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let (events, received) = std::sync::mpsc::channel();
+        let mut kwin = Kwin::connect_with(bus.builder(), events).expect("connect KWin bridge");
+        let windows = kwin.windows().expect("windows");
+
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].app_id.as_deref(), Some("org.kde.konsole"));
+        assert_eq!(windows[0].title.as_deref(), Some("~ : zsh"));
+        assert_eq!(windows[0].pid, Some(4242));
+        assert!(windows[0].is_focused);
+        assert_eq!(windows[1].app_id.as_deref(), Some("firefox"));
+        assert!(!windows[1].is_focused);
+
+        // Poll for the action the way the loaded script does.
+        let address = bus.address.clone();
+        let script = thread::spawn(move || {
+            let conn = Builder::address(address.as_str()).unwrap().build().unwrap();
+            conn.call_method(
+                Some(BUS_NAME),
+                OBJECT_PATH,
+                Some(BUS_NAME),
+                "NextAction",
+                &(),
+            )
+            .unwrap()
+            .body()
+            .deserialize::<String>()
+            .unwrap()
+        });
+        kwin.focus_window(windows[1].id).expect("focus");
+        let action = serde_json::from_str::<serde_json::Value>(&script.join().unwrap()).unwrap();
+        assert_eq!(
+            action,
+            serde_json::json!({
+                "id": "{0b6f6a4e-1111-4c3b-9a55-000000000002}",
+                "action": "activate",
+            })
+        );
+        assert_eq!(loaded.lock().unwrap().len(), 1);
+
+        // The script reports the activation back, which becomes a focus event.
+        let script = Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+        script
+            .call_method(
+                Some(BUS_NAME),
+                OBJECT_PATH,
+                Some(BUS_NAME),
+                "WindowActivated",
+                &("{0b6f6a4e-1111-4c3b-9a55-000000000002}",),
+            )
+            .unwrap();
+        assert_eq!(
+            received.recv_timeout(Duration::from_secs(1)),
+            Ok(WindowEvent::Focused(windows[1].id))
+        );
+        assert!(kwin.windows().unwrap()[1].is_focused);
+    }
+
+    #[test]
+    fn window_ids_are_stable_per_uuid() {
+        let a = window_id("{0b6f6a4e-1111-4c3b-9a55-000000000001}");
+        let b = window_id("{0b6f6a4e-1111-4c3b-9a55-000000000002}");
+        assert_ne!(a, b);
+        assert_eq!(a, window_id("{0b6f6a4e-1111-4c3b-9a55-000000000001}"));
+    }
+}
@@ -0,0 +1,101 @@
+// Loaded into KWin by `witcher --daemon --backend kwin`. Reports the window
+// list and focus changes to the witcher daemon over the session bus, and
+// activates, closes or minimizes the windows the daemon asks for.
+
+const SERVICE = "io.github.iondodon.Witcher.KWin";
+const PATH = "/io/github/iondodon/Witcher/KWin";
+const INTERFACE = "io.github.iondodon.Witcher.KWin";
+
+function windowList() {
+    if (typeof workspace.windowList === "function") {
+        return workspace.windowList();
+    }
+    return workspace.clientList();
+}
+
+function activeWindow() {
+    if (workspace.activeWindow !== undefined) {
+        return workspace.activeWindow;
+    }
+    return workspace.activeClient;
+}
+
+function isSwitchable(window) {
+    return window && window.normalWindow && !window.skipTaskbar && !window.skipSwitcher;
+}
+
+function describe(window, active) {
+    return {
+        id: window.internalId.toString(),
+        resourceClass: String(window.resourceClass || ""),
+        caption: String(window.caption || ""),
+        pid: window.pid > 0 ? window.pid : null,
+        active: window === active,
+    };
+}
+
+function pushWindows() {
+    const active = activeWindow();
+    const windows = windowList().filter(isSwitchable).map(function (window) {
+        return describe(window, active);
+    });
+    callDBus(SERVICE, PATH, INTERFACE, "UpdateWindows", JSON.stringify(windows));
+}
+
+function pushActivated(window) {
+    if (!isSwitchable(window)) {
+        return;
+    }
+    callDBus(SERVICE, PATH, INTERFACE, "WindowActivated", window.internalId.toString());
+    pushWindows();
+}
+
+function watch(window) {
+    if (window && window.captionChanged) {
+        window.captionChanged.connect(pushWindows);
+    }
+}
+
+const added = workspace.windowAdded || workspace.clientAdded;
+const removed = workspace.windowRemoved || workspace.clientRemoved;
+const activated = workspace.windowActivated || workspace.clientActivated;
+
+added.connect(function (window) {
+    watch(window);
+    pushWindows();
+});
+removed.connect(pushWindows);
+activated.connect(pushActivated);
+
+function perform(request) {
+    const window = windowList().find(function (window) {
+        return window.internalId.toString() === request.id;
+    });
+    if (!window) {
+        return;
+    }
+    if (request.action === "close") {
+        window.closeWindow();
+    } else if (request.action === "minimize") {
+        window.minimized = true;
+    } else if (workspace.activeWindow !== undefined) {
+        workspace.activeWindow = window;
+    } else {
+        workspace.activeClient = window;
+    }
+}
+
+// Keeps one NextAction call pending. The daemon answers it with the next
+// window action, or with an empty string before the call would time out.
+function waitForAction() {
+    callDBus(SERVICE, PATH, INTERFACE, "NextAction", function (reply) {
+        if (reply) {
+            perform(JSON.parse(reply));
+        }
+        waitForAction();
+    });
+}
+
+windowList().forEach(watch);
+pushWindows();
+waitForAction();
@@ -0,0 +1,416 @@
+use anyhow::{Context, Result};
+use niri_ipc::{Action, Request, Response, socket::Socket};
+use serde::Deserialize;
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::{Arc, Mutex, mpsc::Sender},
+    thread,
+};
+
+use super::{Backend, BackendWindow, Place, WindowEvent, non_empty, process_name};
+
+/// niri answers a single request per connection, so the long-lived
+/// connection is the event stream, which keeps the window list current.
+pub struct Niri {
+    state: Arc<Mutex<NiriState>>,
+}
+
+#[derive(Default)]
+struct NiriState {
+    /// `None` until the event stream delivered the first window list, and
+    /// again after the stream broke.
+    windows: Option<Vec<NiriWindow>>,
+    /// Like `windows`, from the workspace events.
+    workspaces: Option<Vec<NiriWorkspace>>,
+}
+
+#[derive(Clone, Deserialize)]
+struct NiriWindow {
+    id: u64,
+    title: Option<String>,
+    app_id: Option<String>,
+    pid: Option<i64>,
+    workspace_id: Option<u64>,
+    is_focused: bool,
+}
+
+#[derive(Clone, Deserialize)]
+struct NiriWorkspace {
+    id: u64,
+    output: Option<String>,
+    is_active: bool,
+    is_focused: bool,
+}
+
+#[derive(Deserialize)]
+enum NiriEvent {
+    WindowsChanged { windows: Vec<NiriWindow> },
+    WindowOpenedOrChanged { window: NiriWindow },
+    WindowClosed { id: u64 },
+    WindowFocusChanged { id: Option<u64> },
+    WorkspacesChanged { workspaces: Vec<NiriWorkspace> },
+    WorkspaceActivated { id: u64, focused: bool },
+}
+
+impl NiriEvent {
+    fn window_event(&self) -> Option<WindowEvent> {
+        match self {
+            NiriEvent::WindowFocusChanged { id: Some(id) } => Some(WindowEvent::Focused(*id)),
+            NiriEvent::WindowClosed { id } => Some(WindowEvent::Closed(*id)),
+            _ => None,
+        }
+    }
+}
+
+impl NiriState {
+    fn apply(&mut self, event: NiriEvent) {
+        match event {
+            NiriEvent::WindowsChanged { windows } => {
+                self.windows = Some(windows);
+                return;
+            }
+            NiriEvent::WorkspacesChanged { workspaces } => {
+                self.workspaces = Some(workspaces);
+                return;
+            }
+            NiriEvent::WorkspaceActivated { id, focused } => {
+                self.activate_workspace(id, focused);
+                return;
+            }
+            _ => {}
+        }
+        let Some(windows) = self.windows.as_mut() else {
+            return;
+        };
+        match event {
+            NiriEvent::WindowsChanged { .. }
+            | NiriEvent::WorkspacesChanged { .. }
+            | NiriEvent::WorkspaceActivated { .. } => {}
+            NiriEvent::WindowOpenedOrChanged { window } => {
+                if window.is_focused {
+                    for existing in windows.iter_mut() {
+                        existing.is_focused = false;
+                    }
+                }
+                match windows.iter_mut().find(|existing| existing.id == window.id) {
+                    Some(existing) => *existing = window,
+                    None => windows.push(window),
+                }
+            }
+            NiriEvent::WindowClosed { id } => windows.retain(|window| window.id != id),
+            NiriEvent::WindowFocusChanged { id } => {
+                for window in windows.iter_mut() {
+                    window.is_focused = Some(window.id) == id;
+                }
+            }
+        }
+    }
+}
+
+impl NiriState {
+    /// Makes `id` the active workspace of its output, and the focused one
+    /// when `focused`.
+    fn activate_workspace(&mut self, id: u64, focused: bool) {
+        let Some(workspaces) = self.workspaces.as_mut() else {
+            return;
+        };
+        let Some(output) = workspaces
+            .iter()
+            .find(|workspace| workspace.id == id)
+            .map(|workspace| workspace.output.clone())
+        else {
+            return;
+        };
+        for workspace in workspaces.iter_mut() {
+            if workspace.output == output {
+                workspace.is_active = workspace.id == id;
+            }
+            if focused {
+                workspace.is_focused = workspace.id == id;
+            }
+        }
+    }
+}
+
+fn socket_path() -> Result<PathBuf> {
+    std::env::var_os(niri_ipc::socket::SOCKET_PATH_ENV)
+        .map(PathBuf::from)
+        .context("NIRI_SOCKET is not set, are you running this within niri?")
+}
+
+/// Sends one request and returns the raw `Ok` payload. Used where the
+/// `niri_ipc` types lag behind what niri sends (window pids).
+fn request_json(request: &Request) -> Result<serde_json::Value> {
+    let mut stream = UnixStream::connect(socket_path()?).context("connect to niri socket")?;
+    let mut buf = serde_json::to_string(request).context("serialize niri request")?;
+    buf.push('\n');
+    stream.write_all(buf.as_bytes()).context("send niri request")?;
+    stream.shutdown(Shutdown::Write).context("send niri request")?;
+
+    buf.clear();
+    BufReader::new(stream)
+        .read_line(&mut buf)
+        .context("read niri reply")?;
+    let mut reply = serde_json::from_str::<serde_json::Value>(&buf).context("parse niri reply")?;
+    if let Some(message) = reply.get("Err") {
+        return Err(anyhow::anyhow!(
+            "{}",
+            message.as_str().unwrap_or("niri request failed")
+        ));
+    }
+    Ok(reply.get_mut("Ok").map(serde_json::Value::take).unwrap_or_default())
+}
+
+fn spawn_event_stream(state: Arc<Mutex<NiriState>>, events: Sender<WindowEvent>) -> Result<()> {
+    let mut stream = UnixStream::connect(socket_path()?).context("connect to niri socket")?;
+    let mut buf = serde_json::to_string(&Request::EventStream).context("serialize niri request")?;
+    buf.push('\n');
+    stream
+        .write_all(buf.as_bytes())
+        .context("request niri event stream")?;
+    stream
+        .shutdown(Shutdown::Write)
+        .context("request niri event stream")?;
+
+    let mut reader = BufReader::new(stream);
+    buf.clear();
+    reader
+        .read_line(&mut buf)
+        .context("read niri event stream reply")?;
+    if !buf.contains("Ok") {
+        return Err(anyhow::anyhow!("niri refused event stream: {}", buf.trim()));
+    }
+
+    thread::spawn(move || {
+        read_events(reader, &state, &events);
+        eprintln!("witcher: niri event stream closed");
+    });
+    Ok(())
+}
+
+/// Applies events until the stream ends, then drops the cached state so
+/// that later lookups ask niri directly.
+fn read_events(reader: impl BufRead, state: &Mutex<NiriState>, events: &Sender<WindowEvent>) {
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        // Events this version does not care about fail to parse and are skipped.
+        if let Ok(event) = serde_json::from_str::<NiriEvent>(&line) {
+            if let Some(window_event) = event.window_event() {
+                let _ = events.send(window_event);
+            }
+            state.lock().unwrap().apply(event);
+        }
+    }
+    let mut state = state.lock().unwrap();
+    state.windows = None;
+    state.workspaces = None;
+}
+
+impl Niri {
+    pub fn connect(events: Sender<WindowEvent>) -> Result<Self> {
+        let state = Arc::new(Mutex::new(NiriState::default()));
+        spawn_event_stream(state.clone(), events)?;
+        Ok(Self { state })
+    }
+
+    fn fetch_windows() -> Result<Vec<NiriWindow>> {
+        let mut reply = request_json(&Request::Windows)?;
+        let windows = reply
+            .get_mut("Windows")
+            .map(serde_json::Value::take)
+            .context("unexpected niri reply to Windows")?;
+        serde_json::from_value(windows).context("parse niri windows")
+    }
+
+    fn fetch_workspaces() -> Result<Vec<NiriWorkspace>> {
+        let mut reply = request_json(&Request::Workspaces)?;
+        let workspaces = reply
+            .get_mut("Workspaces")
+            .map(serde_json::Value::take)
+            .context("unexpected niri reply to Workspaces")?;
+        serde_json::from_value(workspaces).context("parse niri workspaces")
+    }
+
+    fn workspaces(&self) -> Result<Vec<NiriWorkspace>> {
+        let cached = self.state.lock().unwrap().workspaces.clone();
+        match cached {
+            Some(workspaces) => Ok(workspaces),
+            None => Self::fetch_workspaces(),
+        }
+    }
+}
+
+impl Backend for Niri {
+    fn windows(&mut self) -> Result<Vec<BackendWindow>> {
+        let cached = self.state.lock().unwrap().windows.clone();
+        let windows = match cached {
+            Some(windows) => windows,
+            None => Self::fetch_windows()?,
+        };
+        // Outputs only matter for filtering, so a failed lookup leaves them
+        // unknown instead of failing the listing.
+        let workspaces = self.workspaces().unwrap_or_default();
+        Ok(windows
+            .into_iter()
+            .map(|window| BackendWindow {
+                id: window.id,
+                title: non_empty(window.title),
+                app_id: non_empty(window.app_id),
+                pid: window.pid,
+                process_name: process_name(window.pid),
+                is_focused: window.is_focused,
+                workspace: window.workspace_id,
+                output: workspaces
+                    .iter()
+                    .find(|workspace| Some(workspace.id) == window.workspace_id)
+                    .and_then(|workspace| workspace.output.clone()),
+            })
+            .collect())
+    }
+
+    fn focused_place(&mut self) -> Result<Place> {
+        Ok(self
+            .workspaces()?
+            .into_iter()
+            .find(|workspace| workspace.is_focused)
+            .map(|workspace| Place {
+                workspace: Some(workspace.id),
+                output: workspace.output,
+            })
+            .unwrap_or_default())
+    }
+
+    fn focus_window(&mut self, id: u64) -> Result<()> {
+        let socket = Socket::connect().context("connect to niri socket")?;
+        let (reply, _events) = socket
+            .send(Request::Action(Action::FocusWindow { id }))
+            .context("send focus request")?;
+        match reply {
+            Ok(_) => Ok(()),
+            Err(message) => Err(anyhow::anyhow!(message)),
+        }
+    }
+
+    fn close_window(&mut self, id: u64) -> Result<()> {
+        let socket = Socket::connect().context("connect to niri socket")?;
+        let (reply, _events) = socket
+            .send(Request::Action(Action::CloseWindow { id: Some(id) }))
+            .context("send close request")?;
+        match reply {
+            Ok(_) => Ok(()),
+            Err(message) => Err(anyhow::anyhow!(message)),
+        }
+    }
+
+    fn focused_output_info(&mut self) -> Result<(Option<(i32, i32)>, u32)> {
+        let socket = Socket::connect().context("connect to niri socket")?;
+        let (reply, _events) = socket
+            .send(Request::FocusedOutput)
+            .context("send focused output request")?;
+        let output = match reply {
+            Ok(Response::FocusedOutput(output)) => output,
+            Ok(_) => None,
+            Err(message) => return Err(anyhow::anyhow!(message)),
+        };
+        if let Some(output) = output
+            && let Some(logical) = output.logical
+        {
+            let scale = logical.scale.max(1.0).round() as u32;
+            return Ok((Some((logical.width as i32, logical.height as i32)), scale.max(1)));
+        }
+        Ok((None, 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_lines(state: &mut NiriState, lines: &[&str]) -> Vec<WindowEvent> {
+        let mut window_events = Vec::new();
+        for line in lines {
+            if let Ok(event) = serde_json::from_str::<NiriEvent>(line) {
+                window_events.extend(event.window_event());
+                state.apply(event);
+            }
+        }
+        window_events
+    }
+
+    fn ids_and_focus(state: &NiriState) -> Vec<(u64, bool)> {
+        state
+            .windows
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|window| (window.id, window.is_focused))
+            .collect()
+    }
+
+    #[test]
+    fn event_stream_keeps_window_list_current() {
+        let mut state = NiriState::default();
+        apply_lines(
+            &mut state,
+            &[
+                r#"{"WindowFocusChanged":{"id":1}}"#,
+                r#"{"WorkspacesChanged":{"workspaces":[]}}"#,
+                r#"{"WindowsChanged":{"windows":[
+                    {"id":1,"title":"a","app_id":"foot","pid":10,"workspace_id":1,"is_focused":true},
+                    {"id":2,"title":"b","app_id":"firefox","pid":20,"workspace_id":1,"is_focused":false}
+                ]}}"#,
+                r#"{"SomeFutureEvent":{"value":1}}"#,
+            ],
+        );
+        assert_eq!(ids_and_focus(&state), vec![(1, true), (2, false)]);
+
+        let window_events = apply_lines(
+            &mut state,
+            &[
+                r#"{"WindowOpenedOrChanged":{"window":
+                    {"id":3,"title":"c","app_id":"foot","pid":30,"workspace_id":1,"is_focused":true}}}"#,
+                r#"{"WindowClosed":{"id":1}}"#,
+            ],
+        );
+        assert_eq!(ids_and_focus(&state), vec![(2, false), (3, true)]);
+        assert_eq!(window_events, vec![WindowEvent::Closed(1)]);
+
+        let window_events = apply_lines(
+            &mut state,
+            &[
+                r#"{"WindowFocusChanged":{"id":2}}"#,
+                r#"{"WindowFocusChanged":{"id":null}}"#,
+            ],
+        );
+        assert_eq!(ids_and_focus(&state), vec![(2, false), (3, false)]);
+        assert_eq!(window_events, vec![WindowEvent::Focused(2)]);
+    }
+
+    #[test]
+    fn closed_stream_drops_cached_state() {
+        let state = Mutex::new(NiriState::default());
+        let (events, received) = std::sync::mpsc::channel();
+        let stream = concat!(
+            r#"{"WorkspacesChanged":{"workspaces":[{"id":1,"output":"DP-1","is_active":true,"is_focused":true}]}}"#,
+            "\n",
+            r#"{"WindowsChanged":{"windows":[]}}"#,
+            "\n",
+            r#"{"WindowClosed":{"id":4}}"#,
+            "\n",
+        );
+        read_events(stream.as_bytes(), &state, &events);
+
+        let state = state.lock().unwrap();
+        assert!(state.windows.is_none());
+        assert!(state.workspaces.is_none());
+        assert_eq!(
+            received.try_iter().collect::<Vec<_>>(),
+            [WindowEvent::Closed(4)]
+        );
+    }
+}
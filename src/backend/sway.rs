@@ -0,0 +1,416 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+    thread,
+};
+
+use super::{Backend, BackendWindow, Place, WindowCache, non_empty, process_name};
+
+const IPC_MAGIC: &[u8; 6] = b"i3-ipc";
+const IPC_HEADER_LEN: usize = IPC_MAGIC.len() + 8;
+
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+
+/// Keeps one connection for requests and a second one subscribed to window
+/// and workspace events, which invalidate the cached tree walk.
+pub struct Sway {
+    ipc: SwayIpc,
+    cache: WindowCache,
+}
+
+struct SwayIpc {
+    stream: UnixStream,
+}
+
+impl SwayIpc {
+    fn connect() -> Result<Self> {
+        let path = std::env::var_os("SWAYSOCK")
+            .context("SWAYSOCK is not set, are you running this within sway?")?;
+        Self::connect_to(Path::new(&path))
+    }
+
+    fn connect_to(path: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(path)
+            .with_context(|| format!("connect to sway socket {}", path.display()))?;
+        Ok(Self { stream })
+    }
+
+    fn send(&mut self, message_type: u32, payload: &[u8]) -> Result<()> {
+        let mut message = Vec::with_capacity(IPC_HEADER_LEN + payload.len());
+        message.extend_from_slice(IPC_MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(payload);
+        self.stream
+            .write_all(&message)
+            .context("write sway ipc message")
+    }
+
+    fn read_message(&mut self) -> Result<(u32, Vec<u8>)> {
+        let mut header = [0u8; IPC_HEADER_LEN];
+        self.stream
+            .read_exact(&mut header)
+            .context("read sway ipc header")?;
+        if &header[..IPC_MAGIC.len()] != IPC_MAGIC {
+            return Err(anyhow::anyhow!("invalid sway ipc reply magic"));
+        }
+        let len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+        let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+        let mut body = vec![0u8; len];
+        self.stream
+            .read_exact(&mut body)
+            .context("read sway ipc payload")?;
+        Ok((message_type, body))
+    }
+
+    fn request(&mut self, message_type: u32, payload: &[u8]) -> Result<Vec<u8>> {
+        self.send(message_type, payload)?;
+        let (reply_type, body) = self.read_message()?;
+        if reply_type != message_type {
+            return Err(anyhow::anyhow!(
+                "unexpected sway ipc reply type {reply_type} (expected {message_type})"
+            ));
+        }
+        Ok(body)
+    }
+
+    fn request_json<T: for<'de> Deserialize<'de>>(
+        &mut self,
+        message_type: u32,
+        payload: &[u8],
+    ) -> Result<T> {
+        let body = self.request(message_type, payload)?;
+        let value = serde_json::from_slice(&body).context("parse sway ipc json")?;
+        Ok(value)
+    }
+}
+
+impl Sway {
+    pub fn connect() -> Result<Self> {
+        let ipc = SwayIpc::connect()?;
+        let cache = WindowCache::new();
+
+        let mut events = SwayIpc::connect()?;
+        let reply =
+            events.request_json::<SwayCommandResult>(SUBSCRIBE, br#"["window", "workspace"]"#)?;
+        if !reply.success {
+            return Err(anyhow::anyhow!("sway refused event subscription"));
+        }
+        let listener = cache.listener();
+        thread::spawn(move || {
+            while events.read_message().is_ok() {
+                listener.invalidate();
+            }
+            eprintln!("witcher: sway event connection closed");
+        });
+
+        Ok(Self { ipc, cache })
+    }
+}
+
+impl Backend for Sway {
+    fn windows(&mut self) -> Result<Vec<BackendWindow>> {
+        let ipc = &mut self.ipc;
+        self.cache.get_or_fetch(|| windows(ipc))
+    }
+
+    fn focus_window(&mut self, id: u64) -> Result<()> {
+        focus_window(&mut self.ipc, id)
+    }
+
+    fn close_window(&mut self, id: u64) -> Result<()> {
+        window_command(&mut self.ipc, id, "kill")
+    }
+
+    fn minimize_window(&mut self, id: u64) -> Result<()> {
+        window_command(&mut self.ipc, id, "move scratchpad")
+    }
+
+    fn focused_output_info(&mut self) -> Result<(Option<(i32, i32)>, u32)> {
+        focused_output_info(&mut self.ipc)
+    }
+
+    fn focused_place(&mut self) -> Result<Place> {
+        focused_place(&mut self.ipc)
+    }
+}
+
+#[derive(Deserialize)]
+struct SwayNode {
+    id: u64,
+    #[serde(rename = "type")]
+    node_type: Option<String>,
+    name: Option<String>,
+    #[serde(default)]
+    focused: bool,
+    app_id: Option<String>,
+    pid: Option<i64>,
+    window_properties: Option<SwayWindowProperties>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+#[derive(Deserialize)]
+struct SwayWindowProperties {
+    class: Option<String>,
+    instance: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SwayWorkspace {
+    id: u64,
+    #[serde(default)]
+    focused: bool,
+    output: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SwayOutput {
+    #[serde(default)]
+    focused: bool,
+    scale: Option<f64>,
+    rect: SwayRect,
+}
+
+#[derive(Deserialize)]
+struct SwayRect {
+    width: i32,
+    height: i32,
+}
+
+#[derive(Deserialize)]
+struct SwayCommandResult {
+    success: bool,
+    error: Option<String>,
+}
+
+impl SwayNode {
+    fn is_window(&self) -> bool {
+        let is_container = matches!(self.node_type.as_deref(), Some("con" | "floating_con"));
+        is_container
+            && self.nodes.is_empty()
+            && self.floating_nodes.is_empty()
+            && (self.pid.is_some() || self.app_id.is_some() || self.window_properties.is_some())
+    }
+
+    fn is_scratchpad(&self) -> bool {
+        matches!(self.node_type.as_deref(), Some("output" | "workspace"))
+            && self
+                .name
+                .as_deref()
+                .is_some_and(|name| name.starts_with("__i3"))
+    }
+}
+
+fn windows(ipc: &mut SwayIpc) -> Result<Vec<BackendWindow>> {
+    let tree = ipc.request_json::<SwayNode>(GET_TREE, b"")?;
+    let mut windows = Vec::new();
+    collect_windows(tree, &Place::default(), &mut windows);
+    Ok(windows)
+}
+
+/// Walks the tree below `node`, which lies in `place`: the workspace and
+/// output nodes above it.
+fn collect_windows(node: SwayNode, place: &Place, windows: &mut Vec<BackendWindow>) {
+    if node.is_scratchpad() {
+        return;
+    }
+    let mut place = place.clone();
+    match node.node_type.as_deref() {
+        Some("output") => place.output = node.name.clone(),
+        Some("workspace") => place.workspace = Some(node.id),
+        _ => {}
+    }
+    if node.is_window() {
+        let app_id = non_empty(node.app_id).or_else(|| {
+            node.window_properties
+                .and_then(|props| non_empty(props.class).or_else(|| non_empty(props.instance)))
+        });
+        windows.push(BackendWindow {
+            id: node.id,
+            title: non_empty(node.name),
+            app_id,
+            pid: node.pid,
+            process_name: process_name(node.pid),
+            is_focused: node.focused,
+            workspace: place.workspace,
+            output: place.output,
+        });
+        return;
+    }
+    for child in node.nodes.into_iter().chain(node.floating_nodes) {
+        collect_windows(child, &place, windows);
+    }
+}
+
+fn focused_place(ipc: &mut SwayIpc) -> Result<Place> {
+    let workspaces = ipc.request_json::<Vec<SwayWorkspace>>(GET_WORKSPACES, b"")?;
+    Ok(workspaces
+        .into_iter()
+        .find(|workspace| workspace.focused)
+        .map(|workspace| Place {
+            workspace: Some(workspace.id),
+            output: workspace.output,
+        })
+        .unwrap_or_default())
+}
+
+fn focus_window(ipc: &mut SwayIpc, id: u64) -> Result<()> {
+    window_command(ipc, id, "focus")
+}
+
+fn window_command(ipc: &mut SwayIpc, id: u64, command: &str) -> Result<()> {
+    let request = format!("[con_id={id}] {command}");
+    let results = ipc.request_json::<Vec<SwayCommandResult>>(RUN_COMMAND, request.as_bytes())?;
+    match results.into_iter().find(|result| !result.success) {
+        Some(result) => Err(anyhow::anyhow!(
+            "sway {command} failed: {}",
+            result.error.unwrap_or_else(|| "unknown error".to_string())
+        )),
+        None => Ok(()),
+    }
+}
+
+fn focused_output_info(ipc: &mut SwayIpc) -> Result<(Option<(i32, i32)>, u32)> {
+    let outputs = ipc.request_json::<Vec<SwayOutput>>(GET_OUTPUTS, b"")?;
+    if let Some(output) = outputs.into_iter().find(|output| output.focused) {
+        let scale = output.scale.unwrap_or(1.0).max(1.0).round() as u32;
+        return Ok((Some((output.rect.width, output.rect.height)), scale.max(1)));
+    }
+    Ok((None, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+
+    const GET_TREE_REPLY: &str = r#"{
+        "id": 1, "type": "root", "name": "root", "focused": false, "nodes": [
+            {"id": 2147483646, "type": "output", "name": "__i3", "nodes": [
+                {"id": 2147483647, "type": "workspace", "name": "__i3_scratch", "nodes": [],
+                 "floating_nodes": [
+                    {"id": 40, "type": "floating_con", "name": "scratch term", "app_id": "foot",
+                     "pid": 4000, "nodes": [], "floating_nodes": []}
+                 ]}
+            ]},
+            {"id": 3, "type": "output", "name": "DP-1", "nodes": [
+                {"id": 4, "type": "workspace", "name": "1", "nodes": [
+                    {"id": 10, "type": "con", "name": "~/src/witcher", "focused": true,
+                     "app_id": "foot", "pid": 1000, "nodes": [], "floating_nodes": []},
+                    {"id": 11, "type": "con", "name": null, "nodes": [
+                        {"id": 12, "type": "con", "name": "Steam", "app_id": null, "pid": 1200,
+                         "shell": "xwayland",
+                         "window_properties": {"class": "steam", "instance": "steamwebhelper",
+                                               "title": "Steam"},
+                         "nodes": [], "floating_nodes": []}
+                    ], "floating_nodes": []}
+                ], "floating_nodes": [
+                    {"id": 13, "type": "floating_con", "name": "Picture-in-Picture",
+                     "app_id": "firefox", "pid": 1300, "nodes": [], "floating_nodes": []}
+                ]}
+            ]}
+        ]
+    }"#;
+
+    fn fake_sway_socket(name: &str, replies: Vec<(u32, &'static str)>) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "witcher-test-sway-{}-{name}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).expect("bind fake sway socket");
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            for (expected_type, reply) in replies {
+                let mut header = [0u8; IPC_HEADER_LEN];
+                stream.read_exact(&mut header).expect("read header");
+                assert_eq!(&header[..6], IPC_MAGIC);
+                let len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+                let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+                assert_eq!(message_type, expected_type);
+                let mut payload = vec![0u8; len];
+                stream.read_exact(&mut payload).expect("read payload");
+
+                let mut message = Vec::new();
+                message.extend_from_slice(IPC_MAGIC);
+                message.extend_from_slice(&(reply.len() as u32).to_ne_bytes());
+                message.extend_from_slice(&message_type.to_ne_bytes());
+                message.extend_from_slice(reply.as_bytes());
+                stream.write_all(&message).expect("write reply");
+            }
+        });
+        path
+    }
+
+    #[test]
+    fn tree_walk_collects_wayland_and_xwayland_windows() {
+        let path = fake_sway_socket("tree", vec![(GET_TREE, GET_TREE_REPLY)]);
+        let mut ipc = SwayIpc::connect_to(&path).expect("connect");
+
+        let windows = windows(&mut ipc).expect("windows");
+        let _ = std::fs::remove_file(&path);
+
+        let ids = windows.iter().map(|w| w.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![10, 12, 13]);
+        assert_eq!(windows[0].app_id.as_deref(), Some("foot"));
+        assert!(windows[0].is_focused);
+        assert_eq!(windows[1].app_id.as_deref(), Some("steam"));
+        assert_eq!(windows[1].title.as_deref(), Some("Steam"));
+        assert_eq!(windows[2].app_id.as_deref(), Some("firefox"));
+        for window in &windows {
+            assert_eq!(window.workspace, Some(4));
+            assert_eq!(window.output.as_deref(), Some("DP-1"));
+        }
+    }
+
+    #[test]
+    fn focus_and_output_requests_use_one_connection() {
+        let path = fake_sway_socket(
+            "focus",
+            vec![
+                (RUN_COMMAND, r#"[{"success": true}]"#),
+                (RUN_COMMAND, r#"[{"success": false, "error": "No matching node."}]"#),
+                (
+                    GET_WORKSPACES,
+                    r#"[{"id": 4, "name": "1", "focused": false, "output": "DP-1"},
+                        {"id": 7, "name": "2", "focused": true, "output": "eDP-1"}]"#,
+                ),
+                (
+                    GET_OUTPUTS,
+                    r#"[{"name": "DP-1", "focused": false, "scale": 1.0,
+                         "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080}},
+                        {"name": "eDP-1", "focused": true, "scale": 2.0,
+                         "rect": {"x": 1920, "y": 0, "width": 1280, "height": 800}}]"#,
+                ),
+            ],
+        );
+        let mut ipc = SwayIpc::connect_to(&path).expect("connect");
+
+        assert!(focus_window(&mut ipc, 10).is_ok());
+        let err = focus_window(&mut ipc, 99).expect_err("focus should fail");
+        assert!(err.to_string().contains("No matching node."));
+        assert_eq!(
+            focused_place(&mut ipc).expect("workspaces"),
+            Place {
+                workspace: Some(7),
+                output: Some("eDP-1".to_string()),
+            }
+        );
+        let info = focused_output_info(&mut ipc).expect("outputs");
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(info, (Some((1280, 800)), 2));
+    }
+}
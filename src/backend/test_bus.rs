@@ -0,0 +1,39 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use zbus::blocking::connection::Builder;
+
+/// A throwaway `dbus-daemon --session` for exercising the D-Bus backends.
+pub struct PrivateBus {
+    daemon: Child,
+    pub address: String,
+}
+
+impl PrivateBus {
+    /// Returns `None` when `dbus-daemon` is not installed.
+    pub fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    pub fn builder(&self) -> Builder<'static> {
+        Builder::address(self.address.as_str()).expect("private bus address")
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
@@ -0,0 +1,344 @@
+use std::fmt;
+use tiny_skia::{
+    Color, GradientStop, LinearGradient, Paint, Point, RadialGradient, SpreadMode, Transform,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    /// The color with its own alpha scaled by `alpha`, which is where the
+    /// `*_opacity` settings come in.
+    pub fn with_alpha(self, alpha: u8) -> Color {
+        let a = (self.a as u32 * alpha as u32 + 127) / 255;
+        Color::from_rgba8(self.r, self.g, self.b, a as u8)
+    }
+}
+
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+/// The CSS basic colors, plus the few extra names people reach for.
+const NAMED_COLORS: [(&str, Rgba); 21] = [
+    ("black", Rgba::rgb(0, 0, 0)),
+    ("silver", Rgba::rgb(192, 192, 192)),
+    ("gray", Rgba::rgb(128, 128, 128)),
+    ("grey", Rgba::rgb(128, 128, 128)),
+    ("white", Rgba::rgb(255, 255, 255)),
+    ("maroon", Rgba::rgb(128, 0, 0)),
+    ("red", Rgba::rgb(255, 0, 0)),
+    ("purple", Rgba::rgb(128, 0, 128)),
+    ("fuchsia", Rgba::rgb(255, 0, 255)),
+    ("magenta", Rgba::rgb(255, 0, 255)),
+    ("green", Rgba::rgb(0, 128, 0)),
+    ("lime", Rgba::rgb(0, 255, 0)),
+    ("olive", Rgba::rgb(128, 128, 0)),
+    ("yellow", Rgba::rgb(255, 255, 0)),
+    ("navy", Rgba::rgb(0, 0, 128)),
+    ("blue", Rgba::rgb(0, 0, 255)),
+    ("teal", Rgba::rgb(0, 128, 128)),
+    ("aqua", Rgba::rgb(0, 255, 255)),
+    ("cyan", Rgba::rgb(0, 255, 255)),
+    ("orange", Rgba::rgb(255, 165, 0)),
+    (
+        "transparent",
+        Rgba {
+            a: 0,
+            ..Rgba::rgb(0, 0, 0)
+        },
+    ),
+];
+
+/// Parses `#rrggbb`, `#rrggbbaa` (the `#` is optional), `rgb(r, g, b)`,
+/// `rgba(r, g, b, a)` with `a` from 0 to 1, or a color name.
+pub fn parse_color(value: &str) -> Result<Rgba, String> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(args) = function_args(&value, "rgba").or_else(|| function_args(&value, "rgb")) {
+        return parse_rgb_function(args);
+    }
+    if let Some((_, color)) = NAMED_COLORS.iter().find(|(name, _)| *name == value) {
+        return Ok(*color);
+    }
+
+    let hex = value.strip_prefix('#').unwrap_or(&value);
+    if !matches!(hex.len(), 6 | 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "expected #rrggbb, #rrggbbaa, rgb(), rgba() or a color name, got `{value}`"
+        ));
+    }
+    let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).unwrap_or(255);
+    Ok(Rgba {
+        r: channel(0),
+        g: channel(2),
+        b: channel(4),
+        a: if hex.len() == 8 { channel(6) } else { 255 },
+    })
+}
+
+fn parse_rgb_function(args: &str) -> Result<Rgba, String> {
+    let args: Vec<&str> = args.split(',').map(str::trim).collect();
+    if !matches!(args.len(), 3 | 4) {
+        return Err(format!(
+            "expected three color components and an optional alpha, got {}",
+            args.len()
+        ));
+    }
+    let channel = |text: &str| {
+        text.parse::<u8>()
+            .map_err(|_| format!("expected a color component from 0 to 255, got `{text}`"))
+    };
+    let alpha = match args.get(3) {
+        Some(text) => match text.parse::<f32>() {
+            Ok(alpha) if (0.0..=1.0).contains(&alpha) => (alpha * 255.0 + 0.5) as u8,
+            _ => return Err(format!("expected an alpha from 0 to 1, got `{text}`")),
+        },
+        None => 255,
+    };
+    Ok(Rgba {
+        r: channel(args[0])?,
+        g: channel(args[1])?,
+        b: channel(args[2])?,
+        a: alpha,
+    })
+}
+
+/// A solid color or a gradient, for the larger surfaces.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fill {
+    Solid(Rgba),
+    /// Evenly spaced colors along a line at `angle` degrees, clockwise from
+    /// pointing up as in CSS.
+    Linear {
+        angle: f32,
+        colors: Vec<Rgba>,
+    },
+    /// Evenly spaced colors from the center out to the corners.
+    Radial {
+        colors: Vec<Rgba>,
+    },
+}
+
+impl Fill {
+    /// The solid color, or the first color of a gradient, for things drawn
+    /// in the fill's color that cannot take a gradient.
+    pub fn base(&self) -> Rgba {
+        match self {
+            Fill::Solid(color) => *color,
+            Fill::Linear { colors, .. } | Fill::Radial { colors } => colors[0],
+        }
+    }
+
+    /// A paint that covers the `width` by `height` box at `(x, y)` with the
+    /// fill, its colors scaled by `alpha`.
+    pub fn paint(&self, alpha: u8, x: f32, y: f32, width: f32, height: f32) -> Paint<'static> {
+        let stops = |colors: &[Rgba]| {
+            let last = (colors.len() - 1) as f32;
+            colors
+                .iter()
+                .enumerate()
+                .map(|(idx, color)| GradientStop::new(idx as f32 / last, color.with_alpha(alpha)))
+                .collect()
+        };
+        let center = Point::from_xy(x + width / 2.0, y + height / 2.0);
+        let shader = match self {
+            Fill::Solid(_) => None,
+            Fill::Linear { angle, colors } => {
+                // Long enough for the corners to get the end colors.
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let half = (width * sin.abs() + height * cos.abs()) / 2.0;
+                LinearGradient::new(
+                    Point::from_xy(center.x - sin * half, center.y + cos * half),
+                    Point::from_xy(center.x + sin * half, center.y - cos * half),
+                    stops(colors),
+                    SpreadMode::Pad,
+                    Transform::identity(),
+                )
+            }
+            Fill::Radial { colors } => RadialGradient::new(
+                center,
+                center,
+                width.hypot(height) / 2.0,
+                stops(colors),
+                SpreadMode::Pad,
+                Transform::identity(),
+            ),
+        };
+
+        let mut paint = Paint::default();
+        match shader {
+            Some(shader) => paint.shader = shader,
+            // Also for boxes too small to hold a gradient.
+            None => paint.set_color(self.base().with_alpha(alpha)),
+        }
+        paint
+    }
+}
+
+impl fmt::Display for Fill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |colors: &[Rgba]| {
+            colors
+                .iter()
+                .map(Rgba::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Fill::Solid(color) => write!(f, "{color}"),
+            Fill::Linear { angle, colors } => {
+                write!(f, "linear-gradient({angle}deg, {})", join(colors))
+            }
+            Fill::Radial { colors } => write!(f, "radial-gradient({})", join(colors)),
+        }
+    }
+}
+
+/// Parses a color, `linear-gradient([angle, ]color, color, ...)` or
+/// `radial-gradient(color, color, ...)`. The angle is in degrees or one of
+/// `to top`, `to right`, `to bottom` and `to left`, and defaults to
+/// `to bottom`.
+pub fn parse_fill(value: &str) -> Result<Fill, String> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(args) = function_args(&value, "linear-gradient") {
+        let mut args = split_args(args);
+        let angle = match parse_angle(args[0])? {
+            Some(angle) => {
+                args.remove(0);
+                angle
+            }
+            None => 180.0,
+        };
+        let colors = parse_stops(&args)?;
+        return Ok(Fill::Linear { angle, colors });
+    }
+    if let Some(args) = function_args(&value, "radial-gradient") {
+        let colors = parse_stops(&split_args(args))?;
+        return Ok(Fill::Radial { colors });
+    }
+    parse_color(&value).map(Fill::Solid)
+}
+
+/// The angle in degrees when `arg` is one rather than a color.
+fn parse_angle(arg: &str) -> Result<Option<f32>, String> {
+    let angle = match arg {
+        "to top" => 0.0,
+        "to right" => 90.0,
+        "to bottom" => 180.0,
+        "to left" => 270.0,
+        _ => match arg.strip_suffix("deg") {
+            Some(degrees) => degrees
+                .trim()
+                .parse()
+                .map_err(|_| format!("expected an angle like `90deg`, got `{arg}`"))?,
+            None => return Ok(None),
+        },
+    };
+    Ok(Some(angle))
+}
+
+fn parse_stops(args: &[&str]) -> Result<Vec<Rgba>, String> {
+    if args.len() < 2 {
+        return Err("a gradient needs at least two colors".to_string());
+    }
+    args.iter().map(|arg| parse_color(arg)).collect()
+}
+
+/// The text between the parentheses of `name(...)`.
+fn function_args<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    value
+        .strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')
+}
+
+/// Splits on the commas that are not inside the parentheses of `rgb()` and
+/// `rgba()`.
+fn split_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(args[start..].trim());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_and_gradients_parse() {
+        let teal = Rgba::rgb(0x11, 0x88, 0x99);
+        assert_eq!(parse_color("#118899"), Ok(teal));
+        assert_eq!(parse_color("118899"), Ok(teal));
+        assert_eq!(parse_color("#11889980"), Ok(Rgba { a: 0x80, ..teal }));
+        assert_eq!(parse_color("rgb(17, 136, 153)"), Ok(teal));
+        assert_eq!(
+            parse_color("RGBA(17,136,153,0.5)"),
+            Ok(Rgba { a: 128, ..teal })
+        );
+        assert_eq!(parse_color("Orange"), Ok(Rgba::rgb(255, 165, 0)));
+        assert!(parse_color("#1188").is_err());
+        assert!(parse_color("rgb(17, 136, 300)").is_err());
+        assert!(parse_color("rgba(17, 136, 153, 2)").is_err());
+        assert!(parse_color("chartreuse-ish").is_err());
+
+        assert_eq!(parse_fill("red"), Ok(Fill::Solid(Rgba::rgb(255, 0, 0))));
+        let linear = parse_fill("linear-gradient(45deg, #111111, rgba(0, 0, 0, 0.5))").unwrap();
+        assert_eq!(
+            linear,
+            Fill::Linear {
+                angle: 45.0,
+                colors: vec![
+                    Rgba::rgb(17, 17, 17),
+                    Rgba {
+                        a: 128,
+                        ..Rgba::rgb(0, 0, 0)
+                    }
+                ],
+            }
+        );
+        assert_eq!(
+            linear.to_string(),
+            "linear-gradient(45deg, #111111, #00000080)"
+        );
+        assert_eq!(parse_fill(&linear.to_string()), Ok(linear));
+        assert!(matches!(
+            parse_fill("linear-gradient(to right, red, blue)"),
+            Ok(Fill::Linear { angle: 90.0, .. })
+        ));
+        assert!(matches!(
+            parse_fill("radial-gradient(white, black, navy)"),
+            Ok(Fill::Radial { colors }) if colors.len() == 3
+        ));
+        assert!(parse_fill("linear-gradient(90deg, red)").is_err());
+        assert!(parse_fill("radial-gradient(red, nope)").is_err());
+    }
+}
@@ -1,12 +1,18 @@
-use std::path::PathBuf;
-use std::sync::OnceLock;
+use anyhow::{Context, Result};
+use inotify::{Inotify, WatchDescriptor, WatchMask, Watches};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use toml::de::{DeTable, DeValue};
 
-#[derive(Clone, Copy, Debug)]
-pub struct Rgb {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
-}
+use crate::color::{self, Fill, Rgba};
+use crate::keys::KeyBindings;
+use crate::rules::Rule;
+use crate::theme;
+use crate::types::WindowFilter;
 
 pub const ICON_SIZE: u32 = 77;
 pub const ICON_SPACING: u32 = 22;
@@ -21,39 +27,80 @@ pub const PANEL_BORDER_OPACITY: f32 = 0.45;
 pub const PANEL_SHADOW_SIZE: f32 = 2.0;
 pub const SELECTED_INDICATOR_BORDER_OPACITY: f32 = 0.24;
 pub const SELECTED_INDICATOR_SHADOW_SIZE: f32 = 0.0;
-pub const PANEL_BACKGROUND_COLOR: Rgb = Rgb {
-    r: 17,
-    g: 17,
-    b: 17,
-};
-pub const PANEL_BORDER_COLOR: Rgb = Rgb {
-    r: 36,
-    g: 36,
-    b: 36,
-};
-pub const PANEL_SHADOW_COLOR: Rgb = Rgb { r: 0, g: 0, b: 0 };
-pub const HOVER_BORDER_COLOR: Rgb = Rgb {
-    r: 72,
-    g: 72,
-    b: 72,
-};
-pub const SELECTED_INDICATOR_COLOR: Rgb = Rgb {
-    r: 255,
-    g: 255,
-    b: 255,
-};
-pub const SELECTED_INDICATOR_BORDER_COLOR: Rgb = Rgb {
-    r: 255,
-    g: 255,
-    b: 255,
-};
-pub const PLACEHOLDER_ICON_COLOR: Rgb = Rgb {
-    r: 90,
-    g: 90,
-    b: 90,
-};
-
-#[derive(Clone, Copy, Debug)]
+pub const PANEL_BACKGROUND_COLOR: Rgba = Rgba::rgb(17, 17, 17);
+pub const PANEL_BORDER_COLOR: Rgba = Rgba::rgb(36, 36, 36);
+pub const PANEL_SHADOW_COLOR: Rgba = Rgba::rgb(0, 0, 0);
+pub const HOVER_BORDER_COLOR: Rgba = Rgba::rgb(72, 72, 72);
+pub const SELECTED_INDICATOR_COLOR: Rgba = Rgba::rgb(255, 255, 255);
+pub const SELECTED_INDICATOR_BORDER_COLOR: Rgba = Rgba::rgb(255, 255, 255);
+pub const PLACEHOLDER_ICON_COLOR: Rgba = Rgba::rgb(90, 90, 90);
+pub const TITLE_MODE: TitleMode = TitleMode::Selected;
+pub const FONT_FAMILY: &str = "sans-serif";
+pub const FONT_SIZE: f32 = 13.0;
+pub const FONT_COLOR: Rgba = Rgba::rgb(235, 235, 235);
+pub const THUMBNAILS: bool = false;
+pub const GROUP_BY_APP: bool = false;
+pub const WINDOW_FILTER: WindowFilter = WindowFilter::All;
+pub const LAYOUT_MODE: LayoutMode = LayoutMode::Grid;
+pub const LIST_WIDTH: u32 = 560;
+pub const COLOR_SCHEME: ColorScheme = ColorScheme::Auto;
+
+/// Which window titles are drawn below the icons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TitleMode {
+    None,
+    Selected,
+    All,
+}
+
+impl TitleMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            TitleMode::None => "none",
+            TitleMode::Selected => "selected",
+            TitleMode::All => "all",
+        }
+    }
+}
+
+/// Whether to use the light or the dark theme.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorScheme {
+    /// Follow the desktop's preference, dark when it has none.
+    Auto,
+    Light,
+    Dark,
+}
+
+impl ColorScheme {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorScheme::Auto => "auto",
+            ColorScheme::Light => "light",
+            ColorScheme::Dark => "dark",
+        }
+    }
+}
+
+/// How windows are arranged on the panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayoutMode {
+    /// Icon tiles, wrapping into rows.
+    Grid,
+    /// One row per window with icon, title and app name.
+    List,
+}
+
+impl LayoutMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            LayoutMode::Grid => "grid",
+            LayoutMode::List => "list",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct AppConfig {
     pub icon_size: u32,
     pub icon_spacing: u32,
@@ -68,16 +115,36 @@ pub struct AppConfig {
     pub panel_shadow_size: f32,
     pub selected_indicator_border_opacity: f32,
     pub selected_indicator_shadow_size: f32,
-    pub panel_background_color: Rgb,
-    pub panel_border_color: Rgb,
-    pub panel_shadow_color: Rgb,
-    pub hover_border_color: Rgb,
-    pub selected_indicator_color: Rgb,
-    pub selected_indicator_border_color: Rgb,
-    pub placeholder_icon_color: Rgb,
+    pub panel_background_color: Fill,
+    pub panel_border_color: Rgba,
+    pub panel_shadow_color: Rgba,
+    pub hover_border_color: Rgba,
+    pub selected_indicator_color: Fill,
+    pub selected_indicator_border_color: Rgba,
+    pub placeholder_icon_color: Rgba,
+    pub title_mode: TitleMode,
+    pub font_family: String,
+    pub font_size: f32,
+    pub font_color: Rgba,
+    pub thumbnails: bool,
+    pub group_by_app: bool,
+    pub window_filter: WindowFilter,
+    pub layout: LayoutMode,
+    pub list_width: u32,
+    /// Theme for both color schemes, unless `light_theme` or `dark_theme`
+    /// names another.
+    pub theme: Option<String>,
+    pub light_theme: Option<String>,
+    pub dark_theme: Option<String>,
+    pub color_scheme: ColorScheme,
+    pub keys: KeyBindings,
+    /// The `[[rules]]` tables, in file order.
+    pub rules: Vec<Rule>,
 }
 
-static CONFIG: OnceLock<AppConfig> = OnceLock::new();
+/// The current config. Reloading swaps in a new `Arc`, so whoever holds the
+/// old one keeps a consistent view of it.
+static CONFIG: RwLock<Option<Arc<AppConfig>>> = RwLock::new(None);
 
 impl Default for AppConfig {
     fn default() -> Self {
@@ -95,50 +162,173 @@ impl Default for AppConfig {
             panel_shadow_size: PANEL_SHADOW_SIZE,
             selected_indicator_border_opacity: SELECTED_INDICATOR_BORDER_OPACITY,
             selected_indicator_shadow_size: SELECTED_INDICATOR_SHADOW_SIZE,
-            panel_background_color: PANEL_BACKGROUND_COLOR,
+            panel_background_color: Fill::Solid(PANEL_BACKGROUND_COLOR),
             panel_border_color: PANEL_BORDER_COLOR,
             panel_shadow_color: PANEL_SHADOW_COLOR,
             hover_border_color: HOVER_BORDER_COLOR,
-            selected_indicator_color: SELECTED_INDICATOR_COLOR,
+            selected_indicator_color: Fill::Solid(SELECTED_INDICATOR_COLOR),
             selected_indicator_border_color: SELECTED_INDICATOR_BORDER_COLOR,
             placeholder_icon_color: PLACEHOLDER_ICON_COLOR,
+            title_mode: TITLE_MODE,
+            font_family: FONT_FAMILY.to_string(),
+            font_size: FONT_SIZE,
+            font_color: FONT_COLOR,
+            thumbnails: THUMBNAILS,
+            group_by_app: GROUP_BY_APP,
+            window_filter: WINDOW_FILTER,
+            layout: LAYOUT_MODE,
+            list_width: LIST_WIDTH,
+            theme: None,
+            light_theme: None,
+            dark_theme: None,
+            color_scheme: COLOR_SCHEME,
+            keys: KeyBindings::default(),
+            rules: Vec::new(),
         }
     }
 }
 
 impl AppConfig {
+    /// Height of one line of text in the panel.
+    pub fn text_row_height(&self) -> u32 {
+        (self.font_size * 1.6).ceil() as u32
+    }
+
+    /// Height of the row below the icons that holds window titles.
+    pub fn title_row_height(&self) -> u32 {
+        match self.title_mode {
+            TitleMode::None => 0,
+            TitleMode::Selected | TitleMode::All => self.text_row_height(),
+        }
+    }
+
+    /// Every setting with its value, as `(section, key, value)` in the order
+    /// the README lists them.
+    pub fn entries(&self) -> Vec<(&'static str, &'static str, toml::Value)> {
+        let number = |value: u32| toml::Value::Integer(value.into());
+        // Through the shortest decimal spelling, so 0.33 does not print as
+        // 0.33000001311302185.
+        let float = |value: f32| toml::Value::Float(value.to_string().parse().unwrap_or(0.0));
+        let text = |value: &str| toml::Value::String(value.to_string());
+        let color = |value: Rgba| text(&value.to_string());
+        let fill = |value: &Fill| text(&value.to_string());
+        let appearance = [
+            ("icon_size", number(self.icon_size)),
+            ("icon_spacing", number(self.icon_spacing)),
+            ("panel_padding", number(self.panel_padding)),
+            ("highlight_padding", number(self.highlight_padding)),
+            ("corner_radius", float(self.corner_radius)),
+            ("border_width", float(self.border_width)),
+            ("indicator_border_width", float(self.indicator_border_width)),
+            ("panel_opacity", float(self.panel_opacity)),
+            (
+                "selected_indicator_opacity",
+                float(self.selected_indicator_opacity),
+            ),
+            ("panel_border_opacity", float(self.panel_border_opacity)),
+            ("panel_shadow_size", float(self.panel_shadow_size)),
+            (
+                "selected_indicator_border_opacity",
+                float(self.selected_indicator_border_opacity),
+            ),
+            (
+                "selected_indicator_shadow_size",
+                float(self.selected_indicator_shadow_size),
+            ),
+            ("panel_background_color", fill(&self.panel_background_color)),
+            ("panel_border_color", color(self.panel_border_color)),
+            ("panel_shadow_color", color(self.panel_shadow_color)),
+            ("hover_border_color", color(self.hover_border_color)),
+            (
+                "selected_indicator_color",
+                fill(&self.selected_indicator_color),
+            ),
+            (
+                "selected_indicator_border_color",
+                color(self.selected_indicator_border_color),
+            ),
+            ("placeholder_icon_color", color(self.placeholder_icon_color)),
+            ("titles", text(self.title_mode.name())),
+            ("font_family", text(&self.font_family)),
+            ("font_size", float(self.font_size)),
+            ("font_color", color(self.font_color)),
+            ("thumbnails", toml::Value::Boolean(self.thumbnails)),
+            ("layout", text(self.layout.name())),
+            ("list_width", number(self.list_width)),
+            ("color_scheme", text(self.color_scheme.name())),
+        ];
+        let themes = [
+            ("theme", &self.theme),
+            ("light_theme", &self.light_theme),
+            ("dark_theme", &self.dark_theme),
+        ];
+        let themes = themes
+            .into_iter()
+            .filter_map(|(key, name)| Some((key, text(name.as_deref()?))));
+        let behavior = [
+            ("group_by_app", toml::Value::Boolean(self.group_by_app)),
+            ("window_filter", text(self.window_filter.name())),
+        ];
+        let appearance = appearance
+            .into_iter()
+            .chain(themes)
+            .map(|(key, value)| ("appearance", key, value));
+        let behavior = behavior
+            .into_iter()
+            .map(|(key, value)| ("behavior", key, value));
+        let keys = self
+            .keys
+            .entries()
+            .into_iter()
+            .map(|(key, value)| ("keys", key, value));
+        appearance.chain(behavior).chain(keys).collect()
+    }
+
     fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
         match key {
-            "icon_size" => self.icon_size = parse_u32(key, value)?,
-            "icon_spacing" => self.icon_spacing = parse_u32(key, value)?,
-            "panel_padding" => self.panel_padding = parse_u32(key, value)?,
-            "highlight_padding" => self.highlight_padding = parse_u32(key, value)?,
-            "corner_radius" => self.corner_radius = parse_f32(key, value)?,
-            "border_width" => self.border_width = parse_f32(key, value)?,
-            "indicator_border_width" => self.indicator_border_width = parse_f32(key, value)?,
-            "panel_opacity" => self.panel_opacity = parse_f32(key, value)?,
+            "icon_size" => self.icon_size = parse_u32(key, value, 16..=512)?,
+            "icon_spacing" => self.icon_spacing = parse_u32(key, value, 0..=256)?,
+            "panel_padding" => self.panel_padding = parse_u32(key, value, 0..=256)?,
+            "highlight_padding" => self.highlight_padding = parse_u32(key, value, 0..=128)?,
+            "corner_radius" => self.corner_radius = parse_f32(key, value, 0.0..=256.0)?,
+            "border_width" => self.border_width = parse_f32(key, value, 0.0..=32.0)?,
+            "indicator_border_width" => {
+                self.indicator_border_width = parse_f32(key, value, 0.0..=32.0)?
+            }
+            "panel_opacity" => self.panel_opacity = parse_f32(key, value, 0.0..=1.0)?,
             "selected_indicator_opacity" => {
-                self.selected_indicator_opacity = parse_f32(key, value)?
+                self.selected_indicator_opacity = parse_f32(key, value, 0.0..=1.0)?
             }
-            "panel_border_opacity" => self.panel_border_opacity = parse_f32(key, value)?,
-            "panel_shadow_size" => self.panel_shadow_size = parse_f32(key, value)?,
+            "panel_border_opacity" => self.panel_border_opacity = parse_f32(key, value, 0.0..=1.0)?,
+            "panel_shadow_size" => self.panel_shadow_size = parse_f32(key, value, 0.0..=128.0)?,
             "selected_indicator_border_opacity" => {
-                self.selected_indicator_border_opacity = parse_f32(key, value)?
+                self.selected_indicator_border_opacity = parse_f32(key, value, 0.0..=1.0)?
             }
             "selected_indicator_shadow_size" => {
-                self.selected_indicator_shadow_size = parse_f32(key, value)?
+                self.selected_indicator_shadow_size = parse_f32(key, value, 0.0..=128.0)?
             }
-            "panel_background_color" => self.panel_background_color = parse_rgb(key, value)?,
+            "panel_background_color" => self.panel_background_color = parse_fill(key, value)?,
             "panel_border_color" => self.panel_border_color = parse_rgb(key, value)?,
             "panel_shadow_color" => self.panel_shadow_color = parse_rgb(key, value)?,
             "hover_border_color" => self.hover_border_color = parse_rgb(key, value)?,
-            "selected_indicator_color" => {
-                self.selected_indicator_color = parse_rgb(key, value)?
-            }
+            "selected_indicator_color" => self.selected_indicator_color = parse_fill(key, value)?,
             "selected_indicator_border_color" => {
                 self.selected_indicator_border_color = parse_rgb(key, value)?
             }
             "placeholder_icon_color" => self.placeholder_icon_color = parse_rgb(key, value)?,
+            "titles" => self.title_mode = parse_title_mode(key, value)?,
+            "font_family" => self.font_family = value.trim_matches('"').to_string(),
+            "font_size" => self.font_size = parse_f32(key, value, 4.0..=128.0)?,
+            "font_color" => self.font_color = parse_rgb(key, value)?,
+            "thumbnails" => self.thumbnails = parse_bool(key, value)?,
+            "group_by_app" => self.group_by_app = parse_bool(key, value)?,
+            "window_filter" => self.window_filter = parse_window_filter(key, value)?,
+            "layout" => self.layout = parse_layout_mode(key, value)?,
+            "list_width" => self.list_width = parse_u32(key, value, 160..=4096)?,
+            "theme" => self.theme = parse_theme_name(value),
+            "light_theme" => self.light_theme = parse_theme_name(value),
+            "dark_theme" => self.dark_theme = parse_theme_name(value),
+            "color_scheme" => self.color_scheme = parse_color_scheme(key, value)?,
             _ => return Err(format!("unknown key `{key}`")),
         }
         Ok(())
@@ -146,122 +336,857 @@ impl AppConfig {
 }
 
 pub fn init() {
-    let _ = CONFIG.set(load());
+    *CONFIG.write().unwrap() = Some(Arc::new(load_or_default()));
+}
+
+pub fn app_config() -> Arc<AppConfig> {
+    if let Some(config) = CONFIG.read().unwrap().as_ref() {
+        return config.clone();
+    }
+    CONFIG
+        .write()
+        .unwrap()
+        .get_or_insert_with(|| Arc::new(load_or_default()))
+        .clone()
+}
+
+/// Re-reads the config file and swaps it in. When the file has errors the
+/// current config stays.
+pub fn reload() -> Result<()> {
+    let loaded = load()?;
+    if !loaded.diagnostics.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{}; keeping the previous config",
+            loaded.errors().join("; ")
+        ));
+    }
+    *CONFIG.write().unwrap() = Some(Arc::new(loaded.config));
+    Ok(())
+}
+
+/// Reloads the config whenever its file, or a file in `themes/`, is
+/// written, replaced or removed.
+pub fn watch() -> Result<()> {
+    let dir = config_dir().context("no config directory, HOME is not set")?;
+    let inotify = Inotify::init().context("init inotify")?;
+    let mut watches = inotify.watches();
+    let mut watched = Vec::new();
+    sync_watches(&mut watches, &dir, &mut watched)
+        .with_context(|| format!("watch {}", dir.display()))?;
+    thread::spawn(move || {
+        let mut inotify = inotify;
+        let mut buffer = [0; 4096];
+        let themes = dir.join("themes");
+        let names = [Format::Toml.file_name(), Format::Flat.file_name()];
+        loop {
+            let events = match inotify.read_events_blocking(&mut buffer) {
+                Ok(events) => events,
+                Err(err) => {
+                    eprintln!("witcher: stopped watching the config: {err}");
+                    break;
+                }
+            };
+            let mut changed = events.into_iter().any(|event| {
+                let Some((_, path)) = watched.iter().find(|(wd, _)| *wd == event.wd) else {
+                    return false;
+                };
+                let name = event
+                    .name
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default();
+                (*path == dir && names.contains(&name))
+                    || (*path == themes && name.ends_with(".toml"))
+            });
+            // The directories may have come or gone, and a config file may
+            // have been written before its new directory was watched.
+            match sync_watches(&mut watches, &dir, &mut watched) {
+                Ok(added) => changed |= added,
+                Err(err) => eprintln!("witcher: failed to watch {}: {err}", dir.display()),
+            }
+            if changed && let Err(err) = reload() {
+                eprintln!("witcher: failed to reload config: {err:#}");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Points the watches at the config directory and its `themes/`, or, while
+/// the config directory does not exist, at the closest ancestor that does,
+/// to see it being created. Editors often save by renaming a new file over
+/// the old one, so directories are watched rather than files. Returns
+/// whether the set of watched directories changed.
+fn sync_watches(
+    watches: &mut Watches,
+    dir: &Path,
+    watched: &mut Vec<(WatchDescriptor, PathBuf)>,
+) -> std::io::Result<bool> {
+    let wanted = if dir.is_dir() {
+        let themes = dir.join("themes");
+        let mut wanted = vec![dir.to_path_buf()];
+        if themes.is_dir() {
+            wanted.push(themes);
+        }
+        wanted
+    } else {
+        dir.ancestors()
+            .skip(1)
+            .find(|ancestor| ancestor.is_dir())
+            .map(Path::to_path_buf)
+            .into_iter()
+            .collect()
+    };
+
+    let before = watched.len();
+    watched.retain(|(wd, path)| {
+        if wanted.contains(path) {
+            return true;
+        }
+        // Fails when the directory is gone, which removed the watch already.
+        let _ = watches.remove(wd.clone());
+        false
+    });
+    let mut changed = watched.len() != before;
+    for path in wanted {
+        if watched.iter().any(|(_, known)| *known == path) {
+            continue;
+        }
+        let mask = WatchMask::CLOSE_WRITE
+            | WatchMask::MOVED_TO
+            | WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::DELETE_SELF;
+        let wd = watches.add(&path, mask)?;
+        watched.push((wd, path));
+        changed = true;
+    }
+    Ok(changed)
+}
+
+/// A problem with one setting, or with the syntax, of a config file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
 }
 
-pub fn app_config() -> &'static AppConfig {
-    CONFIG.get_or_init(load)
+impl Diagnostic {
+    /// A diagnostic pointing at byte `offset` of `text`.
+    fn at(text: &str, offset: usize, message: String) -> Self {
+        let (line, column) = position(text, offset);
+        Self {
+            line,
+            column,
+            message,
+        }
+    }
 }
 
-pub fn icon_size() -> u32 {
-    app_config().icon_size
+/// Line and column, both from 1, of byte `offset` in `text`.
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = text.get(..offset).unwrap_or(text);
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// A config file as read: the resulting config, with the settings that had
+/// problems left at their defaults.
+#[derive(Default)]
+pub struct Loaded {
+    pub config: AppConfig,
+    /// The file read, or `None` when there is none and the defaults apply.
+    pub path: Option<PathBuf>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The settings the file set, as `section.key`, with their line and
+    /// column.
+    pub set_keys: HashMap<String, (usize, usize)>,
+    /// The settings taken from the theme, as `appearance.key`.
+    pub theme_keys: HashSet<String>,
+}
+
+impl Loaded {
+    /// The diagnostics as messages naming the file.
+    pub fn errors(&self) -> Vec<String> {
+        let origin = self
+            .path
+            .as_deref()
+            .map_or_else(|| "config".to_string(), |path| path.display().to_string());
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| format!("failed to parse {origin}:{diagnostic}"))
+            .collect()
+    }
+}
+
+/// Reads the config file, logging its errors.
+fn load_or_default() -> AppConfig {
+    match load() {
+        Ok(loaded) => {
+            for error in loaded.errors() {
+                eprintln!("witcher: {error}");
+            }
+            loaded.config
+        }
+        Err(err) => {
+            eprintln!("witcher: {err:#}");
+            AppConfig::default()
+        }
+    }
+}
+
+/// Reads the config file, or gives the defaults when there is none.
+pub fn load() -> Result<Loaded> {
+    match config_file() {
+        Some((path, format)) => load_file(&path, format),
+        None => Ok(Loaded::default()),
+    }
 }
 
-fn load() -> AppConfig {
-    let Some(path) = config_path() else {
-        return AppConfig::default();
+/// Reads the config file at `path`, in TOML when it ends in `.toml`.
+pub fn load_from(path: &Path) -> Result<Loaded> {
+    let format = if path.extension().is_some_and(|ext| ext == "toml") {
+        Format::Toml
+    } else {
+        Format::Flat
+    };
+    load_file(path, format)
+}
+
+fn load_file(path: &Path, format: Format) -> Result<Loaded> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let mut loaded = match format {
+        Format::Toml => parse_toml(&text),
+        Format::Flat => parse_flat(&text),
+    };
+    loaded.path = Some(path.to_path_buf());
+    apply_theme(&mut loaded, theme::system_color_scheme);
+    Ok(loaded)
+}
+
+/// Fills in the settings of the chosen theme that the file did not set
+/// itself. `system_scheme` is only asked when the scheme matters.
+fn apply_theme(loaded: &mut Loaded, system_scheme: impl FnOnce() -> Option<ColorScheme>) {
+    let config = &loaded.config;
+    let per_scheme = config.light_theme.is_some() || config.dark_theme.is_some();
+    let scheme = match config.color_scheme {
+        ColorScheme::Auto if per_scheme => system_scheme().unwrap_or(ColorScheme::Dark),
+        scheme => scheme,
+    };
+    let (key, name) = match scheme {
+        ColorScheme::Light if config.light_theme.is_some() => ("light_theme", &config.light_theme),
+        ColorScheme::Dark | ColorScheme::Auto if config.dark_theme.is_some() => {
+            ("dark_theme", &config.dark_theme)
+        }
+        _ => ("theme", &config.theme),
     };
-    let text = match std::fs::read_to_string(&path) {
-        Ok(text) => text,
-        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return AppConfig::default(),
+    let Some(name) = name.clone() else {
+        return;
+    };
+    // Problems with the theme are reported where the config names it.
+    let (line, column) = loaded
+        .set_keys
+        .get(&format!("appearance.{key}"))
+        .copied()
+        .unwrap_or((1, 1));
+    let mut diagnose = |message: String| {
+        loaded.diagnostics.push(Diagnostic {
+            line,
+            column,
+            message,
+        });
+    };
+    let (text, origin) = match theme::find(&name) {
+        Ok(found) => found,
         Err(err) => {
-            eprintln!("witcher: failed to read {}: {err}", path.display());
-            return AppConfig::default();
+            diagnose(err);
+            return;
         }
     };
+    let (root, errors) = DeTable::parse_recoverable(&text);
+    for err in errors {
+        let (err_line, err_column) = position(&text, err.span().map_or(0, |span| span.start));
+        diagnose(format!(
+            "{origin}:{err_line}:{err_column}: {}",
+            err.message()
+        ));
+    }
+    for (key, value) in root.get_ref() {
+        let key_name = key.get_ref().as_ref();
+        let setting = format!("appearance.{key_name}");
+        if loaded.set_keys.contains_key(&setting) {
+            continue;
+        }
+        let config = &mut loaded.config;
+        let result = flat_value(key_name, value.get_ref()).and_then(|value| match key_name {
+            "theme" | "light_theme" | "dark_theme" | "color_scheme" => {
+                Err(format!("a theme cannot set `{key_name}`"))
+            }
+            _ if Section::of_key(key_name) == Section::Appearance => config.apply(key_name, &value),
+            _ => Err(format!("unknown key `{key_name}` in a theme")),
+        });
+        match result {
+            Ok(()) => {
+                loaded.theme_keys.insert(setting);
+            }
+            Err(err) => {
+                let (err_line, err_column) = position(&text, key.span().start);
+                diagnose(format!("{origin}:{err_line}:{err_column}: {err}"));
+            }
+        }
+    }
+}
 
-    let mut config = AppConfig::default();
+/// Parses a `config.toml`. Each section is a table whose keys are spelled
+/// like in the flat format.
+fn parse_toml(text: &str) -> Loaded {
+    let mut loaded = Loaded::default();
+    let (root, errors) = DeTable::parse_recoverable(text);
+    for err in errors {
+        let offset = err.span().map_or(0, |span| span.start);
+        loaded
+            .diagnostics
+            .push(Diagnostic::at(text, offset, err.message().to_string()));
+    }
+    // What the parser recovered from a line with a syntax error is a guess,
+    // so settings on those lines are left alone.
+    let broken_lines = loaded
+        .diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.line)
+        .collect::<HashSet<_>>();
+    for (name, table) in root.get_ref() {
+        if name.get_ref() == "rules" {
+            parse_rules(text, &mut loaded, table, name.span().start, &broken_lines);
+            continue;
+        }
+        let section = Section::from_name(name.get_ref());
+        if section == Section::Unknown {
+            let message = format!("unknown section `[{}]`", name.get_ref());
+            loaded
+                .diagnostics
+                .push(Diagnostic::at(text, name.span().start, message));
+            continue;
+        }
+        let Some(table) = table.get_ref().as_table() else {
+            let message = format!("`{0}` must be a table, like `[{0}]`", name.get_ref());
+            loaded
+                .diagnostics
+                .push(Diagnostic::at(text, name.span().start, message));
+            continue;
+        };
+        for (key, value) in table {
+            if broken_lines.contains(&position(text, key.span().start).0) {
+                continue;
+            }
+            let key_name = key.get_ref().as_ref();
+            let config = &mut loaded.config;
+            let result = flat_value(key_name, value.get_ref()).and_then(|value| match section {
+                Section::Keys => config.keys.apply(key_name, &value),
+                section if Section::of_key(key_name) == section => config.apply(key_name, &value),
+                _ => Err(format!(
+                    "unknown key `{key_name}` in `[{}]`",
+                    name.get_ref()
+                )),
+            });
+            match result {
+                Ok(()) => {
+                    loaded.set_keys.insert(
+                        format!("{}.{key_name}", section.name()),
+                        position(text, key.span().start),
+                    );
+                }
+                Err(err) => {
+                    loaded
+                        .diagnostics
+                        .push(Diagnostic::at(text, key.span().start, err));
+                }
+            }
+        }
+    }
+    loaded
+        .diagnostics
+        .sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    loaded
+}
+
+/// Adds the `[[rules]]` tables to the config. A rule with a bad key is
+/// dropped whole, so it cannot end up matching more than it says.
+fn parse_rules(
+    text: &str,
+    loaded: &mut Loaded,
+    tables: &toml::Spanned<DeValue>,
+    offset: usize,
+    broken_lines: &HashSet<usize>,
+) {
+    let Some(tables) = tables.get_ref().as_array() else {
+        let message = "`rules` must be a list of tables, like `[[rules]]`".to_string();
+        loaded
+            .diagnostics
+            .push(Diagnostic::at(text, offset, message));
+        return;
+    };
+    for table in tables {
+        let Some(entries) = table.get_ref().as_table() else {
+            let message = "`rules` must be a list of tables, like `[[rules]]`".to_string();
+            let offset = table.span().start;
+            loaded
+                .diagnostics
+                .push(Diagnostic::at(text, offset, message));
+            continue;
+        };
+        let mut rule = Rule::default();
+        let mut valid = true;
+        for (key, value) in entries {
+            if broken_lines.contains(&position(text, key.span().start).0) {
+                valid = false;
+                continue;
+            }
+            let key_name = key.get_ref().as_ref();
+            if let Err(err) =
+                flat_value(key_name, value.get_ref()).and_then(|value| rule.apply(key_name, &value))
+            {
+                loaded
+                    .diagnostics
+                    .push(Diagnostic::at(text, key.span().start, err));
+                valid = false;
+            }
+        }
+        if !valid {
+            continue;
+        }
+        match rule.check() {
+            Ok(()) => loaded.config.rules.push(rule),
+            Err(err) => {
+                let offset = table.span().start;
+                loaded.diagnostics.push(Diagnostic::at(text, offset, err));
+            }
+        }
+    }
+}
+
+/// Spells a TOML value the way the flat format would, so both formats go
+/// through the same parsing. Lists of keys become comma-separated.
+fn flat_value(key: &str, value: &DeValue) -> Result<String, String> {
+    match value {
+        DeValue::String(text) => Ok(text.to_string()),
+        DeValue::Integer(number) => i64::from_str_radix(number.as_str(), number.radix())
+            .map(|number| number.to_string())
+            .map_err(|err| format!("invalid value for `{key}`: {err}")),
+        DeValue::Float(number) => Ok(number.as_str().to_string()),
+        DeValue::Boolean(value) => Ok(value.to_string()),
+        DeValue::Array(items) if items.is_empty() => Ok("none".to_string()),
+        DeValue::Array(items) => items
+            .iter()
+            .map(|item| match item.get_ref() {
+                DeValue::String(text) => Ok(text.to_string()),
+                other => Err(format!(
+                    "invalid value for `{key}`: expected a list of strings, found {}",
+                    other.type_str()
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|items| items.join(", ")),
+        other => Err(format!(
+            "invalid value for `{key}`: unexpected {}",
+            other.type_str()
+        )),
+    }
+}
+
+/// Parses the older flat `key = value` format.
+fn parse_flat(text: &str) -> Loaded {
+    let mut loaded = Loaded::default();
+    let mut section = None;
     for (idx, raw_line) in text.lines().enumerate() {
         let line = raw_line.trim();
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
+        let column = raw_line.chars().take_while(|ch| ch.is_whitespace()).count() + 1;
+        let mut diagnose = |message: String| {
+            loaded.diagnostics.push(Diagnostic {
+                line: idx + 1,
+                column,
+                message,
+            });
+        };
+
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = match name.trim() {
+                "keys" => Some(Section::Keys),
+                name => {
+                    diagnose(format!("unknown section `[{name}]`"));
+                    Some(Section::Unknown)
+                }
+            };
+            continue;
+        }
 
         let Some((key, value)) = line.split_once('=') else {
-            eprintln!(
-                "witcher: failed to parse {}:{}: expected `key = value`",
-                path.display(),
-                idx + 1
-            );
+            diagnose("expected `key = value`".to_string());
             continue;
         };
 
-        if let Err(err) = config.apply(key.trim(), value.trim()) {
-            eprintln!(
-                "witcher: failed to parse {}:{}: {}",
-                path.display(),
-                idx + 1,
-                err
-            );
+        let (key, value) = (key.trim(), value.trim());
+        let (result, section) = match section {
+            None => (loaded.config.apply(key, value), Section::of_key(key)),
+            Some(Section::Keys) => (loaded.config.keys.apply(key, value), Section::Keys),
+            Some(_) => continue,
+        };
+        match result {
+            Ok(()) => {
+                loaded
+                    .set_keys
+                    .insert(format!("{}.{key}", section.name()), (idx + 1, column));
+            }
+            Err(err) => diagnose(err),
+        }
+    }
+    loaded
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Section {
+    Appearance,
+    Behavior,
+    Keys,
+    Unknown,
+}
+
+impl Section {
+    fn name(self) -> &'static str {
+        match self {
+            Section::Appearance => "appearance",
+            Section::Behavior => "behavior",
+            Section::Keys => "keys",
+            Section::Unknown => "unknown",
+        }
+    }
+
+    fn from_name(name: &str) -> Self {
+        match name {
+            "appearance" => Section::Appearance,
+            "behavior" => Section::Behavior,
+            "keys" => Section::Keys,
+            _ => Section::Unknown,
+        }
+    }
+
+    /// The TOML section a top-level key of the flat format belongs in.
+    fn of_key(key: &str) -> Self {
+        match key {
+            "group_by_app" | "window_filter" => Section::Behavior,
+            _ => Section::Appearance,
         }
     }
-    config
 }
 
-fn config_path() -> Option<PathBuf> {
+/// The two config file formats. `config.toml` wins when both exist.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Toml,
+    /// `key = value` lines with an optional `[keys]` section, as read before
+    /// the TOML format existed.
+    Flat,
+}
+
+impl Format {
+    fn file_name(self) -> &'static str {
+        match self {
+            Format::Toml => "config.toml",
+            Format::Flat => "config",
+        }
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
     if let Some(config_home) = std::env::var_os("XDG_CONFIG_HOME") {
-        return Some(PathBuf::from(config_home).join("witcher").join("config"));
+        return Some(PathBuf::from(config_home).join("witcher"));
     }
     std::env::var_os("HOME")
         .map(PathBuf::from)
-        .map(|home| home.join(".config").join("witcher").join("config"))
+        .map(|home| home.join(".config").join("witcher"))
 }
 
-fn parse_u32(key: &str, value: &str) -> Result<u32, String> {
-    value
-        .parse::<u32>()
-        .map_err(|err| format!("invalid value for `{key}`: {err}"))
+/// The config file to read and its format, when there is one.
+fn config_file() -> Option<(PathBuf, Format)> {
+    let dir = config_dir()?;
+    [Format::Toml, Format::Flat]
+        .into_iter()
+        .map(|format| (dir.join(format.file_name()), format))
+        .find(|(path, _)| path.exists())
 }
 
-fn parse_f32(key: &str, value: &str) -> Result<f32, String> {
-    value
-        .parse::<f32>()
-        .map_err(|err| format!("invalid value for `{key}`: {err}"))
+fn parse_u32(key: &str, value: &str, range: RangeInclusive<u32>) -> Result<u32, String> {
+    let number = value.parse::<i64>().map_err(|_| {
+        format!("invalid value for `{key}`: expected a whole number, got `{value}`")
+    })?;
+    if number < *range.start() as i64 || number > *range.end() as i64 {
+        return Err(format!(
+            "invalid value for `{key}`: expected {} to {}, got {number}",
+            range.start(),
+            range.end()
+        ));
+    }
+    Ok(number as u32)
 }
 
-fn parse_rgb(key: &str, value: &str) -> Result<Rgb, String> {
-    let hex = value.trim().strip_prefix('#').unwrap_or(value.trim());
-    if hex.len() != 6 {
+fn parse_f32(key: &str, value: &str, range: RangeInclusive<f32>) -> Result<f32, String> {
+    let number = value
+        .parse::<f32>()
+        .map_err(|_| format!("invalid value for `{key}`: expected a number, got `{value}`"))?;
+    if !range.contains(&number) {
         return Err(format!(
-            "invalid value for `{key}`: expected 6-digit hex color"
+            "invalid value for `{key}`: expected {} to {}, got {number}",
+            range.start(),
+            range.end()
         ));
     }
+    Ok(number)
+}
 
-    let parse = |range: std::ops::Range<usize>| {
-        u8::from_str_radix(&hex[range], 16)
-            .map_err(|err| format!("invalid value for `{key}`: {err}"))
-    };
+pub fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("invalid value for `{key}`: expected `true` or `false`")),
+    }
+}
 
-    Ok(Rgb {
-        r: parse(0..2)?,
-        g: parse(2..4)?,
-        b: parse(4..6)?,
-    })
+fn parse_rgb(key: &str, value: &str) -> Result<Rgba, String> {
+    color::parse_color(value).map_err(|err| format!("invalid value for `{key}`: {err}"))
 }
 
-pub fn opacity_alpha(value: f32) -> u8 {
-    let clamped = if value < 0.0 {
-        0.0
-    } else if value > 1.0 {
-        1.0
-    } else {
-        value
-    };
-    (clamped * 255.0 + 0.5) as u8
+fn parse_fill(key: &str, value: &str) -> Result<Fill, String> {
+    color::parse_fill(value).map_err(|err| format!("invalid value for `{key}`: {err}"))
+}
+
+fn parse_title_mode(key: &str, value: &str) -> Result<TitleMode, String> {
+    match value {
+        "none" => Ok(TitleMode::None),
+        "selected" => Ok(TitleMode::Selected),
+        "all" => Ok(TitleMode::All),
+        _ => Err(format!(
+            "invalid value for `{key}`: expected `none`, `selected` or `all`"
+        )),
+    }
+}
+
+fn parse_layout_mode(key: &str, value: &str) -> Result<LayoutMode, String> {
+    match value {
+        "grid" => Ok(LayoutMode::Grid),
+        "list" => Ok(LayoutMode::List),
+        _ => Err(format!("invalid value for `{key}`: expected `grid` or `list`")),
+    }
+}
+
+/// A theme name, or `None` for `none` and empty values.
+fn parse_theme_name(value: &str) -> Option<String> {
+    let name = value.trim_matches('"');
+    (!name.is_empty() && name != "none").then(|| name.to_string())
 }
 
-pub fn panel_opacity_alpha() -> u8 {
-    opacity_alpha(app_config().panel_opacity)
+fn parse_color_scheme(key: &str, value: &str) -> Result<ColorScheme, String> {
+    match value {
+        "auto" => Ok(ColorScheme::Auto),
+        "light" => Ok(ColorScheme::Light),
+        "dark" => Ok(ColorScheme::Dark),
+        _ => Err(format!(
+            "invalid value for `{key}`: expected `auto`, `light` or `dark`"
+        )),
+    }
 }
 
-pub fn selected_indicator_alpha() -> u8 {
-    opacity_alpha(app_config().selected_indicator_opacity)
+pub fn parse_window_filter(key: &str, value: &str) -> Result<WindowFilter, String> {
+    WindowFilter::from_name(value).ok_or_else(|| {
+        format!(
+            "invalid value for `{key}`: expected `all`, `current-workspace` or `current-output`"
+        )
+    })
 }
 
-pub fn panel_border_alpha() -> u8 {
-    opacity_alpha(app_config().panel_border_opacity)
+pub fn opacity_alpha(value: f32) -> u8 {
+    let clamped = value.clamp(0.0, 1.0);
+    (clamped * 255.0 + 0.5) as u8
 }
 
-pub fn selected_indicator_border_alpha() -> u8 {
-    opacity_alpha(app_config().selected_indicator_border_opacity)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_reported_and_good_lines_still_apply() {
+        let text = "icon_size = 64\n  icon_spacing = wide\n[keys]\nnext = Tab\n";
+        let loaded = parse_flat(text);
+        assert_eq!(loaded.config.icon_size, 64);
+        assert_eq!(loaded.config.icon_spacing, ICON_SPACING);
+        assert_eq!(
+            loaded.diagnostics,
+            [Diagnostic {
+                line: 2,
+                column: 3,
+                message: "invalid value for `icon_spacing`: expected a whole number, got `wide`"
+                    .to_string(),
+            }]
+        );
+        assert_eq!(loaded.set_keys.get("appearance.icon_size"), Some(&(1, 1)));
+        assert!(loaded.set_keys.contains_key("keys.next"));
+        assert!(!loaded.set_keys.contains_key("appearance.icon_spacing"));
+    }
+
+    #[test]
+    fn toml_sections_apply_and_bad_values_are_rejected() {
+        let text = r#"
+[appearance]
+icon_size = 64
+panel_padding = -3
+ font_size = 200.0
+titles = "all"
+
+[behavior]
+group_by_app = true
+icon_spacing = 10
+
+[keys]
+next = ["Tab", "n"]
+minimize = []
+"#;
+        let loaded = parse_toml(text);
+        let config = &loaded.config;
+        assert_eq!(config.icon_size, 64);
+        assert_eq!(config.panel_padding, PANEL_PADDING);
+        assert_eq!(config.font_size, FONT_SIZE);
+        assert_eq!(config.title_mode, TitleMode::All);
+        assert!(config.group_by_app);
+        let errors = loaded
+            .diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "4:1: invalid value for `panel_padding`: expected 0 to 256, got -3",
+                "5:2: invalid value for `font_size`: expected 4 to 128, got 200",
+                "10:1: unknown key `icon_spacing` in `[behavior]`",
+            ]
+        );
+
+        let loaded = parse_toml("[appearance]\nicon_size = = 3\n");
+        // Only the syntax errors; the broken setting is not applied.
+        assert_eq!(
+            (loaded.diagnostics[0].line, loaded.diagnostics[0].column),
+            (2, 13)
+        );
+        assert!(
+            loaded
+                .diagnostics
+                .iter()
+                .all(|diagnostic| diagnostic.line == 2)
+        );
+        assert!(
+            !loaded
+                .diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.message.contains("icon_size"))
+        );
+    }
+
+    #[test]
+    fn rules_with_bad_keys_are_dropped() {
+        let text = r#"
+[[rules]]
+app_id = "firefox"
+title = "^Picture-in-Picture$"
+hide = true
+
+[[rules]]
+process_name = "steam"
+hide = "sometimes"
+
+[[rules]]
+label = "Terminal"
+"#;
+        let loaded = parse_toml(text);
+        assert_eq!(loaded.config.rules.len(), 1);
+        assert_eq!(loaded.config.rules[0].app_id.as_deref(), Some("firefox"));
+        let errors = loaded
+            .diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "9:1: invalid value for `hide`: expected `true` or `false`",
+                "11:1: a rule needs at least one of `app_id`, `title`, `pid` or `process_name`",
+            ]
+        );
+    }
+
+    #[test]
+    fn themes_fill_in_what_the_file_leaves_unset() {
+        let text = r#"
+[appearance]
+light_theme = "light"
+dark_theme = "nord"
+font_color = "123456"
+"#;
+        let mut loaded = parse_toml(text);
+        apply_theme(&mut loaded, || Some(ColorScheme::Light));
+        assert!(loaded.diagnostics.is_empty(), "{:?}", loaded.diagnostics);
+        let config = &loaded.config;
+        assert_eq!(config.panel_background_color.base().r, 0xf4);
+        assert_eq!(config.panel_opacity, 0.75);
+        assert_eq!(config.font_color.r, 0x12);
+        assert!(loaded.theme_keys.contains("appearance.panel_opacity"));
+        assert!(!loaded.theme_keys.contains("appearance.font_color"));
+
+        let mut loaded = parse_toml("[appearance]\ntheme = \"nope\"\n");
+        apply_theme(&mut loaded, || None);
+        assert_eq!(loaded.diagnostics.len(), 1);
+        assert_eq!(loaded.diagnostics[0].line, 2);
+        assert!(
+            loaded.diagnostics[0]
+                .message
+                .starts_with("unknown theme `nope`")
+        );
+    }
+
+    #[test]
+    fn watches_follow_the_config_directory_into_existence() {
+        let root = std::env::temp_dir().join(format!("witcher-watch-{}", std::process::id()));
+        let dir = root.join("witcher");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let inotify = Inotify::init().unwrap();
+        let mut watches = inotify.watches();
+        let mut watched = Vec::new();
+        let paths = |watched: &Vec<(WatchDescriptor, PathBuf)>| {
+            watched
+                .iter()
+                .map(|(_, path)| path.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(sync_watches(&mut watches, &dir, &mut watched).unwrap());
+        assert_eq!(paths(&watched), [root.as_path()]);
+
+        std::fs::create_dir_all(dir.join("themes")).unwrap();
+        assert!(sync_watches(&mut watches, &dir, &mut watched).unwrap());
+        assert_eq!(paths(&watched), [dir.clone(), dir.join("themes")]);
+        assert!(!sync_watches(&mut watches, &dir, &mut watched).unwrap());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
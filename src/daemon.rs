@@ -1,7 +1,7 @@
 use anyhow::{Context, Result};
 use std::{
     fs::OpenOptions,
-    io::{Read, Write},
+    io::{BufRead, BufReader, Write},
     os::fd::AsRawFd,
     os::unix::net::{UnixListener, UnixStream},
     path::PathBuf,
@@ -11,31 +11,66 @@ use std::{
         Mutex,
     },
     thread,
+    time::{Duration, Instant},
 };
 
+use crate::backend::{self, SharedBackend, WindowEvent};
+use crate::config::{self, app_config};
 use crate::icon::IconCache;
-use crate::mru::MruState;
-use crate::switcher::{run_switcher, SwitcherControl};
-use crate::types::BackendKind;
+use crate::ipc::{self, Command, Direction, Reply, Request};
+use crate::mru::{MruState, MruStore};
+use crate::switcher::{run_switcher, Scope, SwitcherControl};
+use crate::theme;
+use crate::types::{BackendKind, WindowFilter};
 
-pub fn send_show() -> Result<()> {
-    send_command(b"cycle-next")
+pub fn send_show(filter: Option<WindowFilter>) -> Result<()> {
+    send_command(Command::Cycle {
+        direction: Direction::Next,
+        filter,
+    })
 }
 
-pub fn send_show_prev() -> Result<()> {
-    send_command(b"cycle-prev")
+pub fn send_show_prev(filter: Option<WindowFilter>) -> Result<()> {
+    send_command(Command::Cycle {
+        direction: Direction::Prev,
+        filter,
+    })
 }
 
-fn send_command(cmd: &[u8]) -> Result<()> {
-    let socket_path = runtime_socket_path("witcher.sock")?;
-    let mut stream = UnixStream::connect(&socket_path)
-        .with_context(|| format!("connect {}", socket_path.display()))?;
-    let _ = stream.write_all(cmd);
-    let mut buf = [0u8; 8];
-    let _ = stream.read(&mut buf);
-    Ok(())
+pub fn send_show_app(filter: Option<WindowFilter>) -> Result<()> {
+    send_command(Command::CycleApp {
+        direction: Direction::Next,
+        filter,
+    })
 }
 
+pub fn send_show_app_prev(filter: Option<WindowFilter>) -> Result<()> {
+    send_command(Command::CycleApp {
+        direction: Direction::Prev,
+        filter,
+    })
+}
+
+pub fn send_reload() -> Result<()> {
+    send_command(Command::Reload {})
+}
+
+fn send_command(command: Command) -> Result<()> {
+    let reply = ipc::send_request(&Request {
+        version: ipc::PROTOCOL_VERSION,
+        command,
+    })?;
+    if reply.ok {
+        return Ok(());
+    }
+    let error = reply.error.as_deref().unwrap_or("request failed");
+    Err(anyhow::anyhow!("witcher daemon: {error}"))
+}
+
+/// How long window events may go unsaved, so a burst of focus changes
+/// writes the MRU order once.
+const MRU_SAVE_DELAY: Duration = Duration::from_secs(2);
+
 struct SwitcherControlSender {
     tx: mpsc::Sender<SwitcherControl>,
     wake: UnixStream,
@@ -48,69 +83,152 @@ impl SwitcherControlSender {
     }
 }
 
-pub fn run_daemon(backend: BackendKind) -> Result<()> {
+pub fn run_daemon(kind: BackendKind) -> Result<()> {
     let _lock = acquire_daemon_lock()?;
-    let socket_path = runtime_socket_path("witcher.sock")?;
-    let _listener = match bind_listener(&socket_path) {
-        Ok(listener) => listener,
-        Err(err) => return Err(err),
-    };
+    let (event_tx, event_rx) = mpsc::channel::<WindowEvent>();
+    let backend: SharedBackend = Arc::new(Mutex::new(
+        backend::connect(kind, event_tx).context("connect to compositor backend")?,
+    ));
+    let store = Arc::new(backend::instance_key(kind).and_then(MruStore::new));
+    let mru = Arc::new(Mutex::new(load_mru(&store, &backend)));
+    let mru_events = mru.clone();
+    let store_events = store.clone();
+    thread::spawn(move || follow_window_events(event_rx, &mru_events, &store_events));
+    if let Err(err) = config::watch() {
+        eprintln!("witcher: not watching the config for changes: {err:#}");
+    }
+    if let Err(err) = theme::watch_color_scheme() {
+        eprintln!("witcher: not following the color scheme: {err:#}");
+    }
+    let socket_path = runtime_socket_path(ipc::SOCKET_NAME)?;
+    let listener = bind_listener(&socket_path)?;
 
     let (tx, rx) = mpsc::channel::<DaemonMsg>();
     let switcher_sender: Arc<Mutex<Option<SwitcherControlSender>>> = Arc::new(Mutex::new(None));
-    let listener = _listener.try_clone().context("clone listener")?;
-    let tx_listener = tx.clone();
-    let sender_listener = switcher_sender.clone();
+    let ipc = Arc::new(IpcContext {
+        kind,
+        backend: backend.clone(),
+        mru: mru.clone(),
+        store: store.clone(),
+        daemon: Mutex::new(tx),
+        switcher: switcher_sender.clone(),
+    });
     thread::spawn(move || {
         loop {
-            let Ok((mut stream, _)) = listener.accept() else {
+            let Ok((stream, _)) = listener.accept() else {
                 continue;
             };
-            let mut buf = [0u8; 32];
-            let read_len = match stream.read(&mut buf) {
-                Ok(len) => len,
-                Err(_) => 0,
-            };
-            let _ = stream.write_all(b"ok");
-            let msg = parse_socket_msg(&buf[..read_len]);
-            if !try_send_control(&sender_listener, &msg) {
-                let _ = tx_listener.send(msg);
-            }
+            let ipc = ipc.clone();
+            thread::spawn(move || serve_client(&ipc, stream));
         }
     });
 
-    let mut icon_cache = IconCache::default();
-    let mut mru = MruState::default();
+    let mut icon_cache = IconCache::new(app_config());
     loop {
-        let Ok(msg) = rx.recv() else {
-            continue;
+        let (scope, filter) = match rx.recv() {
+            Ok(DaemonMsg::Open(filter)) => (Scope::All, filter),
+            Ok(DaemonMsg::OpenApp(filter)) => (Scope::FocusedApp, filter),
+            Err(_) => continue,
         };
-        if matches!(msg, DaemonMsg::CycleNext | DaemonMsg::CyclePrev) {
-            while rx.try_recv().is_ok() {}
-            let (control_tx, control_rx) = mpsc::channel();
-            let (wake_write, wake_read) = UnixStream::pair().context("create wake pipe")?;
-            {
-                let mut guard = switcher_sender.lock().unwrap();
-                *guard = Some(SwitcherControlSender {
-                    tx: control_tx,
-                    wake: wake_write,
-                });
+        while rx.try_recv().is_ok() {}
+        // Icons are drawn at the configured size, so a reload invalidates
+        // them.
+        let config = app_config();
+        if !Arc::ptr_eq(&config, icon_cache.config()) {
+            icon_cache = IconCache::new(config);
+        }
+        let (control_tx, control_rx) = mpsc::channel();
+        let (wake_write, wake_read) = UnixStream::pair().context("create wake pipe")?;
+        {
+            let mut guard = switcher_sender.lock().unwrap();
+            *guard = Some(SwitcherControlSender {
+                tx: control_tx,
+                wake: wake_write,
+            });
+        }
+        let result = run_switcher(
+            &backend,
+            &mut icon_cache,
+            &mru,
+            scope,
+            filter,
+            control_rx,
+            wake_read,
+        );
+        {
+            let mut guard = switcher_sender.lock().unwrap();
+            *guard = None;
+        }
+        match result {
+            Ok(Some(id)) => {
+                let mut mru = mru.lock().unwrap();
+                mru.update_on_focus(id);
+                save_mru(&store, &mru);
             }
-            let result = run_switcher(backend, &mut icon_cache, &mut mru, control_rx, wake_read);
-            {
-                let mut guard = switcher_sender.lock().unwrap();
-                *guard = None;
+            Ok(None) => {}
+            Err(err) => eprintln!("witcher: switcher error: {err:#}"),
+        }
+        while rx.try_recv().is_ok() {}
+    }
+}
+
+/// Keeps the MRU order current with the backend's window events, saving it
+/// at most once per `MRU_SAVE_DELAY`, and once more when the events stop.
+fn follow_window_events(
+    events: mpsc::Receiver<WindowEvent>,
+    mru: &Mutex<MruState>,
+    store: &Option<MruStore>,
+) {
+    let mut unsaved_since = None::<Instant>;
+    loop {
+        let event = match unsaved_since {
+            Some(since) => events.recv_timeout(MRU_SAVE_DELAY.saturating_sub(since.elapsed())),
+            None => events
+                .recv()
+                .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+        };
+        match event {
+            Ok(event) => {
+                let mut mru = mru.lock().unwrap();
+                match event {
+                    WindowEvent::Focused(id) => mru.update_on_focus(id),
+                    WindowEvent::Closed(id) => mru.remove(id),
+                }
+                unsaved_since.get_or_insert_with(Instant::now);
             }
-            match result {
-                Ok(Some(id)) => mru.update_on_focus(id),
-                Ok(None) => {}
-                Err(err) => eprintln!("witcher: switcher error: {err:#}"),
+            Err(err) => {
+                if unsaved_since.take().is_some() {
+                    save_mru(store, &mru.lock().unwrap());
+                }
+                if err == mpsc::RecvTimeoutError::Disconnected {
+                    break;
+                }
             }
-            while rx.try_recv().is_ok() {}
         }
     }
 }
 
+/// Restores the saved order, minus windows that closed in the meantime.
+fn load_mru(store: &Option<MruStore>, backend: &SharedBackend) -> MruState {
+    let Some(store) = store else {
+        return MruState::default();
+    };
+    let mut mru = store.load();
+    match backend.lock().unwrap().windows() {
+        Ok(windows) => mru.retain_existing(&windows.iter().map(|window| window.id).collect()),
+        Err(err) => eprintln!("witcher: failed to list windows: {err:#}"),
+    }
+    mru
+}
+
+fn save_mru(store: &Option<MruStore>, mru: &MruState) {
+    if let Some(store) = store
+        && let Err(err) = store.save(mru)
+    {
+        eprintln!("witcher: failed to save MRU order: {err:#}");
+    }
+}
+
 struct DaemonLock {
     _file: std::fs::File,
 }
@@ -121,6 +239,7 @@ fn acquire_daemon_lock() -> Result<DaemonLock> {
         .read(true)
         .write(true)
         .create(true)
+        .truncate(false)
         .open(&path)
         .with_context(|| format!("open {}", path.display()))?;
     let rc = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
@@ -134,7 +253,7 @@ fn acquire_daemon_lock() -> Result<DaemonLock> {
     Ok(DaemonLock { _file: file })
 }
 
-fn runtime_socket_path(name: &str) -> Result<PathBuf> {
+pub fn runtime_socket_path(name: &str) -> Result<PathBuf> {
     let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
         .map(PathBuf::from)
         .unwrap_or_else(|| PathBuf::from("/tmp"));
@@ -142,10 +261,7 @@ fn runtime_socket_path(name: &str) -> Result<PathBuf> {
 }
 
 fn bind_listener(path: &PathBuf) -> Result<UnixListener> {
-    if let Ok(mut stream) = UnixStream::connect(path) {
-        let mut buf = [0u8; 8];
-        let _ = stream.write_all(b"ping");
-        let _ = stream.read(&mut buf);
+    if UnixStream::connect(path).is_ok() {
         return Err(anyhow::anyhow!("witcher daemon already running"));
     }
     let _ = std::fs::remove_file(path);
@@ -154,33 +270,145 @@ fn bind_listener(path: &PathBuf) -> Result<UnixListener> {
     Ok(listener)
 }
 
-fn parse_socket_msg(buf: &[u8]) -> DaemonMsg {
-    let text = std::str::from_utf8(buf).unwrap_or("").trim();
-    if text.eq_ignore_ascii_case("cycle-prev") {
-        DaemonMsg::CyclePrev
-    } else {
-        DaemonMsg::CycleNext
+/// What the IPC connection threads need to answer requests.
+struct IpcContext {
+    kind: BackendKind,
+    backend: SharedBackend,
+    mru: Arc<Mutex<MruState>>,
+    store: Arc<Option<MruStore>>,
+    daemon: Mutex<mpsc::Sender<DaemonMsg>>,
+    switcher: Arc<Mutex<Option<SwitcherControlSender>>>,
+}
+
+/// Answers each request line of one connection until the client hangs up.
+fn serve_client(ipc: &IpcContext, stream: UnixStream) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = ipc::parse_request(&line).and_then(|request| handle_request(ipc, request));
+        let Ok(mut reply) = serde_json::to_string(&Reply::from_result(result)) else {
+            break;
+        };
+        reply.push('\n');
+        if writer.write_all(reply.as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+fn handle_request(ipc: &IpcContext, request: Request) -> Result<Option<serde_json::Value>> {
+    match request.command {
+        Command::Cycle { direction, filter } => {
+            let control = match direction {
+                Direction::Next => SwitcherControl::CycleNext,
+                Direction::Prev => SwitcherControl::CyclePrev,
+            };
+            if !try_send_control(&ipc.switcher, control) {
+                open_switcher(ipc, DaemonMsg::Open(resolve_filter(filter)))?;
+            }
+            Ok(None)
+        }
+        Command::CycleApp { direction, filter } => {
+            let control = match direction {
+                Direction::Next => SwitcherControl::CycleAppNext,
+                Direction::Prev => SwitcherControl::CycleAppPrev,
+            };
+            if !try_send_control(&ipc.switcher, control) {
+                open_switcher(ipc, DaemonMsg::OpenApp(resolve_filter(filter)))?;
+            }
+            Ok(None)
+        }
+        Command::Show { filter } => {
+            if ipc.switcher.lock().unwrap().is_none() {
+                open_switcher(ipc, DaemonMsg::Open(resolve_filter(filter)))?;
+            }
+            Ok(None)
+        }
+        Command::Hide {} => {
+            try_send_control(&ipc.switcher, SwitcherControl::Hide);
+            Ok(None)
+        }
+        Command::Select { id } => {
+            let windows = ipc.backend.lock().unwrap().windows()?;
+            if !windows.iter().any(|window| window.id == id) {
+                return Err(anyhow::anyhow!("no window with id {id}"));
+            }
+            if !try_send_control(&ipc.switcher, SwitcherControl::Select(id)) {
+                ipc.backend.lock().unwrap().focus_window(id)?;
+                let mut mru = ipc.mru.lock().unwrap();
+                mru.update_on_focus(id);
+                save_mru(&ipc.store, &mru);
+            }
+            Ok(None)
+        }
+        Command::Status {} => Ok(Some(serde_json::json!({
+            "backend": ipc.kind.name(),
+            "switcher_open": ipc.switcher.lock().unwrap().is_some(),
+        }))),
+        Command::ListWindows {} => {
+            let windows = ipc.backend.lock().unwrap().windows()?;
+            let windows = ipc
+                .mru
+                .lock()
+                .unwrap()
+                .order_by_key(windows, |window| (window.id, window.is_focused));
+            let windows = windows
+                .into_iter()
+                .map(|window| {
+                    serde_json::json!({
+                        "id": window.id,
+                        "app_id": window.app_id,
+                        "title": window.title,
+                        "pid": window.pid,
+                        "workspace": window.workspace,
+                        "output": window.output,
+                        "focused": window.is_focused,
+                    })
+                })
+                .collect();
+            Ok(Some(serde_json::Value::Array(windows)))
+        }
+        Command::Reload {} => {
+            config::reload()?;
+            Ok(None)
+        }
     }
 }
 
+fn resolve_filter(filter: Option<WindowFilter>) -> WindowFilter {
+    filter.unwrap_or(app_config().window_filter)
+}
+
+fn open_switcher(ipc: &IpcContext, msg: DaemonMsg) -> Result<()> {
+    ipc.daemon
+        .lock()
+        .unwrap()
+        .send(msg)
+        .context("daemon is shutting down")
+}
+
 fn try_send_control(
     sender: &Arc<Mutex<Option<SwitcherControlSender>>>,
-    msg: &DaemonMsg,
+    control: SwitcherControl,
 ) -> bool {
     let mut guard = sender.lock().unwrap();
     let Some(sender) = guard.as_mut() else {
         return false;
     };
-    let control = match msg {
-        DaemonMsg::CycleNext => SwitcherControl::CycleNext,
-        DaemonMsg::CyclePrev => SwitcherControl::CyclePrev,
-    };
     sender.send(control);
     true
 }
 
 #[derive(Clone, Copy)]
 enum DaemonMsg {
-    CycleNext,
-    CyclePrev,
+    Open(WindowFilter),
+    /// Opens the switcher on the focused app's windows only.
+    OpenApp(WindowFilter),
 }
@@ -8,34 +8,84 @@ use std::{
     path::{Path, PathBuf},
     sync::Arc,
 };
-use tiny_skia::{Color, IntSize, Paint, Pixmap, Transform};
+use tiny_skia::{IntSize, Paint, Pixmap, Transform};
 
-use crate::config::{app_config, icon_size};
+use crate::color::Rgba;
+use crate::config::AppConfig;
 
-#[derive(Default)]
+/// Icons and app names looked up so far, for one config: icons are drawn
+/// at its `icon_size`, so a reloaded config needs a new cache.
 pub struct IconCache {
+    config: Arc<AppConfig>,
     icons: std::collections::HashMap<String, Arc<Pixmap>>,
+    app_names: std::collections::HashMap<String, Option<String>>,
+    named_icons: std::collections::HashMap<String, Option<Arc<Pixmap>>>,
 }
 
 impl IconCache {
+    pub fn new(config: Arc<AppConfig>) -> Self {
+        Self {
+            config,
+            icons: Default::default(),
+            app_names: Default::default(),
+            named_icons: Default::default(),
+        }
+    }
+
+    pub fn config(&self) -> &Arc<AppConfig> {
+        &self.config
+    }
+
     pub fn icon_for(&mut self, app_id: &str, title: Option<&str>) -> Arc<Pixmap> {
         let cache_key = icon_cache_key(app_id, title);
         if let Some(icon) = self.icons.get(&cache_key) {
             return icon.clone();
         }
-        let icon = load_icon(app_id, title).unwrap_or_else(|_| placeholder_icon(icon_size()));
+        let icon_size = self.config.icon_size;
+        let icon = load_icon(app_id, title, icon_size)
+            .unwrap_or_else(|_| placeholder_icon(icon_size, self.config.placeholder_icon_color));
         let icon = Arc::new(icon);
         self.icons.insert(cache_key, icon.clone());
         icon
     }
+
+    /// The icon a window rule names, from the icon theme or an image path,
+    /// or `None` when it cannot be loaded.
+    pub fn named_icon(&mut self, name: &str) -> Option<Arc<Pixmap>> {
+        let icon_size = self.config.icon_size;
+        self.named_icons
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let path = resolve_icon_path(name, icon_size);
+                match path
+                    .context("no icon found")
+                    .and_then(|path| load_icon_file(&path, icon_size))
+                {
+                    Ok(icon) => Some(Arc::new(icon)),
+                    Err(err) => {
+                        eprintln!("witcher: icon `{name}` from a window rule: {err:#}");
+                        None
+                    }
+                }
+            })
+            .clone()
+    }
+
+    /// The `Name=` of the app's desktop entry, e.g. "Visual Studio Code" for
+    /// `code`.
+    pub fn app_name(&mut self, app_id: &str) -> Option<String> {
+        self.app_names
+            .entry(app_id.to_string())
+            .or_insert_with(|| desktop_app_name(&icon_name_candidates(app_id)))
+            .clone()
+    }
 }
 
 fn icon_cache_key(app_id: &str, title: Option<&str>) -> String {
     format!("{}\t{}", app_id, title.unwrap_or(""))
 }
 
-fn load_icon(app_id: &str, title: Option<&str>) -> Result<Pixmap> {
-    let icon_size = icon_size();
+fn load_icon(app_id: &str, title: Option<&str>, icon_size: u32) -> Result<Pixmap> {
     let mut candidates = icon_name_candidates(app_id);
     if let Some(title) = title {
         let mut seen = candidates.iter().cloned().collect::<HashSet<_>>();
@@ -57,12 +107,15 @@ fn load_icon(app_id: &str, title: Option<&str>) -> Result<Pixmap> {
                 .find()
         })
         .context("no icon found")?;
+    load_icon_file(&path, icon_size)
+}
 
+fn load_icon_file(path: &Path, icon_size: u32) -> Result<Pixmap> {
     if path.extension().and_then(|ext| ext.to_str()) == Some("svg") {
-        return render_svg(&path, icon_size);
+        return render_svg(path, icon_size);
     }
 
-    let image = image::open(&path).with_context(|| format!("open icon {}", path.display()))?;
+    let image = image::open(path).with_context(|| format!("open icon {}", path.display()))?;
     let resized = image.resize_exact(icon_size, icon_size, FilterType::Lanczos3);
     Ok(pixmap_from_image(resized))
 }
@@ -145,11 +198,10 @@ fn pixmap_from_image(image: DynamicImage) -> Pixmap {
     Pixmap::from_vec(bytes, size).expect("pixmap from image")
 }
 
-fn placeholder_icon(size: u32) -> Pixmap {
+fn placeholder_icon(size: u32, color: Rgba) -> Pixmap {
     let mut pixmap = Pixmap::new(size, size).expect("placeholder pixmap");
     let mut paint = Paint::default();
-    let color = app_config().placeholder_icon_color;
-    paint.set_color(Color::from_rgba8(color.r, color.g, color.b, 255));
+    paint.set_color(color.with_alpha(255));
     pixmap.fill_rect(
         tiny_skia::Rect::from_xywh(0.0, 0.0, size as f32, size as f32).unwrap(),
         &paint,
@@ -201,6 +253,19 @@ fn render_svg(path: &Path, size: u32) -> Result<Pixmap> {
 }
 
 fn desktop_icon_name(candidates: &[String]) -> Option<String> {
+    find_desktop_entry(candidates, |info| info.icon.is_some()).and_then(|info| info.icon)
+}
+
+fn desktop_app_name(candidates: &[String]) -> Option<String> {
+    find_desktop_entry(candidates, |info| info.name.is_some()).and_then(|info| info.name)
+}
+
+/// First desktop entry for one of `candidates` that has what `wanted` looks
+/// for. Entries named after a candidate win over ones that only mention it.
+fn find_desktop_entry(
+    candidates: &[String],
+    wanted: impl Fn(&DesktopEntryInfo) -> bool,
+) -> Option<DesktopEntryInfo> {
     let paths = application_dirs();
 
     for base in &paths {
@@ -210,12 +275,10 @@ fn desktop_icon_name(candidates: &[String]) -> Option<String> {
             } else {
                 base.join(format!("{name}.desktop"))
             };
-            if let Ok(info) = parse_desktop_entry(&file) {
-                if let Some(info) = info {
-                    if let Some(icon) = info.icon {
-                        return Some(icon);
-                    }
-                }
+            if let Ok(Some(info)) = parse_desktop_entry(&file)
+                && wanted(&info)
+            {
+                return Some(info);
             }
         }
     }
@@ -252,10 +315,8 @@ fn desktop_icon_name(candidates: &[String]) -> Option<String> {
                 .exec_names
                 .iter()
                 .any(|exec| candidates_lower.contains(&exec.to_ascii_lowercase()));
-            if matches_name || matches_startup || matches_exec {
-                if let Some(icon) = info.icon {
-                    return Some(icon);
-                }
+            if (matches_name || matches_startup || matches_exec) && wanted(&info) {
+                return Some(info);
             }
         }
     }
@@ -403,11 +464,14 @@ fn icon_path_score(path: &Path, icon_size: u32) -> u32 {
         .map(|size| size.abs_diff(icon_size))
         .min()
         .unwrap_or(icon_size);
-    let symbolic_penalty = path
+    let symbolic_penalty = if path
         .components()
         .any(|component| component.as_os_str().to_string_lossy().contains("symbolic"))
-        .then_some(1)
-        .unwrap_or(0);
+    {
+        1
+    } else {
+        0
+    };
 
     size_delta * 2 + symbolic_penalty
 }
@@ -426,6 +490,7 @@ fn push_path(paths: &mut Vec<PathBuf>, seen: &mut HashSet<PathBuf>, path: PathBu
 
 struct DesktopEntryInfo {
     icon: Option<String>,
+    name: Option<String>,
     names: Vec<String>,
     startup_wm_class: Option<String>,
     exec_names: Vec<String>,
@@ -438,6 +503,7 @@ fn parse_desktop_entry(path: &Path) -> Result<Option<DesktopEntryInfo>> {
     };
     let mut in_entry = false;
     let mut icon = None;
+    let mut name = None;
     let mut names = Vec::new();
     let mut startup_wm_class = None;
     let mut exec_names = Vec::new();
@@ -461,6 +527,9 @@ fn parse_desktop_entry(path: &Path) -> Result<Option<DesktopEntryInfo>> {
             continue;
         }
         if let Some(value) = desktop_name_value(line) {
+            if line.starts_with("Name=") {
+                name = Some(value.to_string());
+            }
             names.push(value.to_string());
             continue;
         }
@@ -483,6 +552,7 @@ fn parse_desktop_entry(path: &Path) -> Result<Option<DesktopEntryInfo>> {
     }
     Ok(Some(DesktopEntryInfo {
         icon,
+        name,
         names,
         startup_wm_class,
         exec_names,
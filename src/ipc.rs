@@ -0,0 +1,192 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+
+use crate::daemon::runtime_socket_path;
+use crate::types::WindowFilter;
+
+/// Bumped when a request or reply changes incompatibly.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub const SOCKET_NAME: &str = "witcher.sock";
+
+/// One line on `witcher.sock`, e.g. `{"version":1,"command":"cycle","direction":"prev"}`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Request {
+    #[serde(default = "current_version")]
+    pub version: u32,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    Next,
+    Prev,
+}
+
+/// Commands without arguments are empty struct variants, since
+/// `deny_unknown_fields` lets unit variants through with extra fields.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Command {
+    /// Opens the switcher, or moves the selection when it is already open.
+    Cycle {
+        #[serde(default)]
+        direction: Direction,
+        /// Overrides `window_filter` from the config when opening.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filter: Option<WindowFilter>,
+    },
+    /// Like `cycle`, but only among the windows of the focused window's app.
+    CycleApp {
+        #[serde(default)]
+        direction: Direction,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filter: Option<WindowFilter>,
+    },
+    /// Opens the switcher without moving the selection of an open one.
+    Show {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filter: Option<WindowFilter>,
+    },
+    /// Closes the switcher without focusing anything.
+    Hide {},
+    /// Focuses the window, closing the switcher if it is open.
+    Select {
+        id: u64,
+    },
+    Status {},
+    ListWindows {},
+    /// Re-reads the config file.
+    Reload {},
+}
+
+/// The answer to one request, also one line of JSON.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Reply {
+    pub version: u32,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl Reply {
+    pub fn from_result(result: Result<Option<serde_json::Value>>) -> Self {
+        match result {
+            Ok(data) => Self {
+                version: PROTOCOL_VERSION,
+                ok: true,
+                error: None,
+                data,
+            },
+            Err(err) => Self {
+                version: PROTOCOL_VERSION,
+                ok: false,
+                error: Some(format!("{err:#}")),
+                data: None,
+            },
+        }
+    }
+}
+
+fn current_version() -> u32 {
+    PROTOCOL_VERSION
+}
+
+pub fn parse_request(line: &str) -> Result<Request> {
+    let request = serde_json::from_str::<Request>(line).context("invalid request")?;
+    if request.version > PROTOCOL_VERSION {
+        return Err(anyhow::anyhow!(
+            "unsupported protocol version {} (daemon speaks {PROTOCOL_VERSION})",
+            request.version
+        ));
+    }
+    Ok(request)
+}
+
+/// Sends one request to the daemon and waits for its reply.
+pub fn send_request(request: &Request) -> Result<Reply> {
+    let mut line = serde_json::to_string(request).context("serialize request")?;
+    line.push('\n');
+    send_line(&line)
+}
+
+/// Sends a request given as raw JSON, as scripts do with `witcher --ipc`.
+pub fn send_raw(json: &str) -> Result<Reply> {
+    let mut line = json.trim().to_string();
+    line.push('\n');
+    send_line(&line)
+}
+
+fn send_line(line: &str) -> Result<Reply> {
+    let socket_path = runtime_socket_path(SOCKET_NAME)?;
+    let mut stream = UnixStream::connect(&socket_path)
+        .with_context(|| format!("connect {}", socket_path.display()))?;
+    stream.write_all(line.as_bytes()).context("send request")?;
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .context("read reply")?;
+    serde_json::from_str(&reply).context("invalid reply")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_parse_and_reject_unknown_commands() {
+        let request = parse_request(r#"{"version":1,"command":"cycle","direction":"prev"}"#)
+            .expect("cycle request");
+        assert_eq!(
+            request.command,
+            Command::Cycle {
+                direction: Direction::Prev,
+                filter: None,
+            }
+        );
+        let request = parse_request(r#"{"command":"select","id":42}"#).expect("select request");
+        assert_eq!(request.version, PROTOCOL_VERSION);
+        assert_eq!(request.command, Command::Select { id: 42 });
+        assert_eq!(
+            parse_request(r#"{"command":"list-windows"}"#)
+                .unwrap()
+                .command,
+            Command::ListWindows {}
+        );
+        assert_eq!(
+            parse_request(r#"{"command":"cycle-app"}"#).unwrap().command,
+            Command::CycleApp {
+                direction: Direction::Next,
+                filter: None,
+            }
+        );
+        assert_eq!(
+            parse_request(r#"{"command":"show","filter":"current-output"}"#)
+                .unwrap()
+                .command,
+            Command::Show {
+                filter: Some(WindowFilter::CurrentOutput)
+            }
+        );
+        assert!(parse_request(r#"{"command":"show","filter":"elsewhere"}"#).is_err());
+
+        assert!(parse_request(r#"{"command":"explode"}"#).is_err());
+        assert!(parse_request(r#"{"command":"select","id":1,"bogus":1}"#).is_err());
+        assert!(parse_request(r#"{"command":"hide","bogus":1}"#).is_err());
+        assert!(parse_request("cycle-next").is_err());
+        assert!(parse_request(r#"{"version":99,"command":"status"}"#).is_err());
+
+        let reply = Reply::from_result(Err(anyhow::anyhow!("no window with id 7")));
+        assert_eq!(
+            serde_json::to_string(&reply).unwrap(),
+            r#"{"version":1,"ok":false,"error":"no window with id 7"}"#
+        );
+    }
+}
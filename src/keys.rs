@@ -0,0 +1,347 @@
+use smithay_client_toolkit::seat::keyboard::{Keysym, Modifiers};
+use xkbcommon::xkb;
+
+/// The modifier held while the switcher is open. Releasing it focuses the
+/// selected window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HoldModifier {
+    Alt,
+    Super,
+    Ctrl,
+    Shift,
+}
+
+impl HoldModifier {
+    pub fn name(self) -> &'static str {
+        match self {
+            HoldModifier::Alt => "alt",
+            HoldModifier::Super => "super",
+            HoldModifier::Ctrl => "ctrl",
+            HoldModifier::Shift => "shift",
+        }
+    }
+
+    pub fn is_held(self, modifiers: &Modifiers) -> bool {
+        match self {
+            HoldModifier::Alt => modifiers.alt,
+            HoldModifier::Super => modifiers.logo,
+            HoldModifier::Ctrl => modifiers.ctrl,
+            HoldModifier::Shift => modifiers.shift,
+        }
+    }
+
+    pub fn is_key(self, keysym: Keysym) -> bool {
+        match self {
+            HoldModifier::Alt => matches!(keysym, Keysym::Alt_L | Keysym::Alt_R),
+            HoldModifier::Super => matches!(keysym, Keysym::Super_L | Keysym::Super_R),
+            HoldModifier::Ctrl => matches!(keysym, Keysym::Control_L | Keysym::Control_R),
+            HoldModifier::Shift => matches!(keysym, Keysym::Shift_L | Keysym::Shift_R),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    Next,
+    Prev,
+    Accept,
+    Cancel,
+    First,
+    Last,
+    Close,
+    Kill,
+    Minimize,
+    /// Opens the window list of the selected app when grouping by app.
+    Expand,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl KeyAction {
+    const ALL: [(&'static str, KeyAction); 14] = [
+        ("next", KeyAction::Next),
+        ("prev", KeyAction::Prev),
+        ("accept", KeyAction::Accept),
+        ("cancel", KeyAction::Cancel),
+        ("first", KeyAction::First),
+        ("last", KeyAction::Last),
+        ("close", KeyAction::Close),
+        ("kill", KeyAction::Kill),
+        ("minimize", KeyAction::Minimize),
+        ("expand", KeyAction::Expand),
+        ("left", KeyAction::Left),
+        ("right", KeyAction::Right),
+        ("up", KeyAction::Up),
+        ("down", KeyAction::Down),
+    ];
+}
+
+/// Modifiers a binding needs on top of the hold modifier.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ModifierMask {
+    shift: bool,
+    ctrl: bool,
+    alt: bool,
+    logo: bool,
+}
+
+impl ModifierMask {
+    fn count(self) -> usize {
+        [self.shift, self.ctrl, self.alt, self.logo]
+            .into_iter()
+            .filter(|held| *held)
+            .count()
+    }
+
+    fn is_subset_of(self, other: ModifierMask) -> bool {
+        (!self.shift || other.shift)
+            && (!self.ctrl || other.ctrl)
+            && (!self.alt || other.alt)
+            && (!self.logo || other.logo)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyCombo {
+    keysym: Keysym,
+    modifiers: ModifierMask,
+}
+
+impl KeyCombo {
+    /// The combo as written in the config, e.g. `shift+Tab`.
+    fn name(&self) -> String {
+        let prefixes = [
+            (self.modifiers.shift, "shift+"),
+            (self.modifiers.ctrl, "ctrl+"),
+            (self.modifiers.alt, "alt+"),
+            (self.modifiers.logo, "super+"),
+        ];
+        let mut name = prefixes
+            .into_iter()
+            .filter(|(held, _)| *held)
+            .map(|(_, prefix)| prefix)
+            .collect::<String>();
+        name.push_str(&xkb::keysym_get_name(self.keysym));
+        name
+    }
+}
+
+/// The `[keys]` section of the config.
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    pub modifier: HoldModifier,
+    bindings: Vec<(KeyAction, Vec<KeyCombo>)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let defaults = [
+            (KeyAction::Next, "Tab"),
+            (KeyAction::Prev, "shift+Tab"),
+            (KeyAction::Accept, "Return, KP_Enter"),
+            (KeyAction::Cancel, "Escape"),
+            (KeyAction::First, "Home"),
+            (KeyAction::Last, "End"),
+            (KeyAction::Close, "q, Delete"),
+            (KeyAction::Kill, "shift+Delete"),
+            (KeyAction::Minimize, "none"),
+            (KeyAction::Expand, "grave"),
+            (KeyAction::Left, "Left"),
+            (KeyAction::Right, "Right"),
+            (KeyAction::Up, "Up"),
+            (KeyAction::Down, "Down"),
+        ];
+        Self {
+            modifier: HoldModifier::Alt,
+            bindings: defaults
+                .into_iter()
+                .map(|(action, combos)| {
+                    (action, parse_combos(combos).expect("default key bindings"))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if key == "modifier" {
+            self.modifier = parse_hold_modifier(key, value)?;
+            return Ok(());
+        }
+        let Some((_, action)) = KeyAction::ALL.iter().find(|(name, _)| *name == key) else {
+            return Err(format!("unknown key `{key}` in [keys]"));
+        };
+        let combos =
+            parse_combos(value).map_err(|err| format!("invalid value for `{key}`: {err}"))?;
+        match self.bindings.iter_mut().find(|(bound, _)| bound == action) {
+            Some((_, bound)) => *bound = combos,
+            None => self.bindings.push((*action, combos)),
+        }
+        Ok(())
+    }
+
+    /// The modifier and every action with its keys, in the order the README
+    /// lists them.
+    pub fn entries(&self) -> Vec<(&'static str, toml::Value)> {
+        let mut entries = vec![(
+            "modifier",
+            toml::Value::String(self.modifier.name().to_string()),
+        )];
+        for (name, action) in KeyAction::ALL {
+            let combos = self
+                .bindings
+                .iter()
+                .filter(|(bound, _)| *bound == action)
+                .flat_map(|(_, combos)| combos)
+                .map(|combo| toml::Value::String(combo.name()))
+                .collect();
+            entries.push((name, toml::Value::Array(combos)));
+        }
+        entries
+    }
+
+    /// The action bound to a key press. When several bindings match, the
+    /// one naming the most modifiers wins, so `shift+Tab` beats `Tab`.
+    pub fn action(&self, keysym: Keysym, modifiers: &Modifiers) -> Option<KeyAction> {
+        let (keysym, implied_shift) = normalize(keysym);
+        let held = ModifierMask {
+            shift: modifiers.shift || implied_shift,
+            ctrl: modifiers.ctrl,
+            alt: modifiers.alt,
+            logo: modifiers.logo,
+        };
+        self.bindings
+            .iter()
+            .flat_map(|(action, combos)| combos.iter().map(move |combo| (*action, combo)))
+            .filter(|(_, combo)| combo.keysym == keysym && combo.modifiers.is_subset_of(held))
+            .max_by_key(|(_, combo)| combo.modifiers.count())
+            .map(|(action, _)| action)
+    }
+}
+
+/// Folds the keysyms that Shift produces back to the base key, so a
+/// binding written as `shift+Tab` matches `ISO_Left_Tab`.
+fn normalize(keysym: Keysym) -> (Keysym, bool) {
+    if keysym == Keysym::ISO_Left_Tab {
+        return (Keysym::Tab, true);
+    }
+    match keysym.key_char() {
+        Some(ch) if ch.is_uppercase() => {
+            let lower = ch.to_lowercase().next().unwrap_or(ch);
+            (Keysym::from_char(lower), true)
+        }
+        _ => (keysym, false),
+    }
+}
+
+fn parse_hold_modifier(key: &str, value: &str) -> Result<HoldModifier, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "alt" => Ok(HoldModifier::Alt),
+        "super" | "logo" | "mod4" => Ok(HoldModifier::Super),
+        "ctrl" | "control" => Ok(HoldModifier::Ctrl),
+        "shift" => Ok(HoldModifier::Shift),
+        _ => Err(format!(
+            "invalid value for `{key}`: expected `alt`, `super`, `ctrl` or `shift`"
+        )),
+    }
+}
+
+/// Parses a comma-separated list such as `Tab, ctrl+n`. `none` unbinds.
+fn parse_combos(value: &str) -> Result<Vec<KeyCombo>, String> {
+    if value.trim().eq_ignore_ascii_case("none") {
+        return Ok(Vec::new());
+    }
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|combo| !combo.is_empty())
+        .map(parse_combo)
+        .collect()
+}
+
+fn parse_combo(combo: &str) -> Result<KeyCombo, String> {
+    let mut parts = combo.split('+').map(str::trim).collect::<Vec<_>>();
+    let name = parts.pop().filter(|name| !name.is_empty()).ok_or_else(|| {
+        format!("`{combo}` has no key")
+    })?;
+    let mut modifiers = ModifierMask::default();
+    for part in parts {
+        match part.to_ascii_lowercase().as_str() {
+            "shift" => modifiers.shift = true,
+            "ctrl" | "control" => modifiers.ctrl = true,
+            "alt" => modifiers.alt = true,
+            "super" | "logo" | "mod4" => modifiers.logo = true,
+            _ => return Err(format!("unknown modifier `{part}` in `{combo}`")),
+        }
+    }
+    let (keysym, implied_shift) = normalize(keysym_from_name(name)?);
+    modifiers.shift |= implied_shift;
+    Ok(KeyCombo { keysym, modifiers })
+}
+
+fn keysym_from_name(name: &str) -> Result<Keysym, String> {
+    let keysym = xkb::keysym_from_name(name, xkb::KEYSYM_NO_FLAGS);
+    if keysym != Keysym::NoSymbol {
+        return Ok(keysym);
+    }
+    let keysym = xkb::keysym_from_name(name, xkb::KEYSYM_CASE_INSENSITIVE);
+    if keysym != Keysym::NoSymbol {
+        return Ok(keysym);
+    }
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Ok(Keysym::from_char(ch)),
+        _ => Err(format!("unknown key name `{name}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(shift: bool, ctrl: bool) -> Modifiers {
+        Modifiers {
+            alt: true,
+            shift,
+            ctrl,
+            ..Modifiers::default()
+        }
+    }
+
+    #[test]
+    fn bindings_match_the_most_specific_combo() {
+        let mut keys = KeyBindings::default();
+        assert_eq!(
+            keys.action(Keysym::Tab, &held(false, false)),
+            Some(KeyAction::Next)
+        );
+        assert_eq!(
+            keys.action(Keysym::ISO_Left_Tab, &held(true, false)),
+            Some(KeyAction::Prev)
+        );
+
+        keys.apply("modifier", "super").unwrap();
+        keys.apply("next", "Tab, ctrl+n").unwrap();
+        keys.apply("prev", "ctrl+p, K").unwrap();
+        keys.apply("first", "none").unwrap();
+        assert_eq!(keys.modifier, HoldModifier::Super);
+        assert_eq!(
+            keys.action(Keysym::n, &held(false, true)),
+            Some(KeyAction::Next)
+        );
+        assert_eq!(keys.action(Keysym::n, &held(false, false)), None);
+        assert_eq!(
+            keys.action(Keysym::K, &held(true, false)),
+            Some(KeyAction::Prev)
+        );
+        assert_eq!(keys.action(Keysym::Home, &held(false, false)), None);
+
+        assert!(keys.apply("next", "hyper+Tab").is_err());
+        assert!(keys.apply("next", "NotAKey").is_err());
+        assert!(keys.apply("explode", "Tab").is_err());
+        assert!(keys.apply("modifier", "meta").is_err());
+    }
+}
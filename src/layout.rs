@@ -0,0 +1,408 @@
+use crate::config::{AppConfig, LayoutMode};
+
+/// A move of the selection with the arrow keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Positions of the windows on the panel, shared by drawing, the selection
+/// indicator, pointer hit testing and arrow key navigation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    Grid(GridLayout),
+    List(ListLayout),
+}
+
+impl Layout {
+    /// Lays out `count` windows starting `top` pixels down the panel, sized
+    /// to fit an output of `output_size` when that is known.
+    pub fn new(
+        mode: LayoutMode,
+        count: usize,
+        top: u32,
+        output_size: Option<(i32, i32)>,
+        config: &AppConfig,
+    ) -> Self {
+        // Leave a margin between the panel and the output edges.
+        let margin = config.panel_padding * 2;
+        let max_width = output_size.map(|(width, _)| (width.max(0) as u32).saturating_sub(margin));
+        let max_height =
+            output_size.map(|(_, height)| (height.max(0) as u32).saturating_sub(margin));
+        match mode {
+            LayoutMode::Grid => {
+                Layout::Grid(GridLayout::new(count, top, max_width, max_height, config))
+            }
+            LayoutMode::List => {
+                Layout::List(ListLayout::new(count, top, max_width, max_height, config))
+            }
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            Layout::Grid(grid) => (grid.width, grid.height),
+            Layout::List(list) => (list.width, list.height),
+        }
+    }
+
+    /// Top-left corner of item `idx`, or `None` when it is not shown.
+    pub fn item_position(&self, idx: usize) -> Option<(i32, i32)> {
+        match self {
+            Layout::Grid(grid) => grid.tile_position(idx),
+            Layout::List(list) => list.row_position(idx),
+        }
+    }
+
+    pub fn hit_test(&self, position: (f64, f64)) -> Option<usize> {
+        match self {
+            Layout::Grid(grid) => grid.hit_test(position),
+            Layout::List(list) => list.hit_test(position),
+        }
+    }
+
+    pub fn neighbor(&self, idx: usize, step: Step) -> Option<usize> {
+        match self {
+            Layout::Grid(grid) => grid.neighbor(idx, step),
+            Layout::List(list) => list.neighbor(idx, step),
+        }
+    }
+
+    /// Scrolls so item `idx` is shown.
+    pub fn scroll_to(&mut self, idx: usize) {
+        match self {
+            Layout::Grid(grid) => grid.scroll_to(idx),
+            Layout::List(list) => list.scroll_to(idx),
+        }
+    }
+}
+
+/// Where the tiles go. Tiles wrap into as many rows as the output width
+/// needs, each row centered, with room for titles below every row. Rows
+/// that do not fit the output height scroll.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridLayout {
+    count: usize,
+    columns: usize,
+    pub width: u32,
+    pub height: u32,
+    item_size: u32,
+    spacing: u32,
+    row_pitch: u32,
+    top: u32,
+    visible_rows: usize,
+    first_row: usize,
+}
+
+impl GridLayout {
+    /// Lays out `count` tiles starting `top` pixels down the panel, in a
+    /// panel no larger than `max_width` by `max_height` when those are known.
+    pub fn new(
+        count: usize,
+        top: u32,
+        max_width: Option<u32>,
+        max_height: Option<u32>,
+        config: &AppConfig,
+    ) -> Self {
+        let item_size = config.icon_size + config.highlight_padding * 2;
+        let spacing = config.icon_spacing;
+        let padding = config.panel_padding;
+        let title_row = config.title_row_height();
+        let fitting = max_width
+            .map(|max_width| {
+                let usable = max_width.saturating_sub(padding * 2) + spacing;
+                (usable / (item_size + spacing)).max(1) as usize
+            })
+            .unwrap_or(usize::MAX);
+        let columns = count.min(fitting).max(1);
+        let rows = count.div_ceil(columns).max(1);
+        // Rows are kept apart by their titles, or by the tile spacing when
+        // there are none.
+        let row_gap = if title_row > 0 { title_row } else { spacing };
+        let row_pitch = item_size + row_gap;
+        let visible_rows = max_height
+            .map(|max| {
+                let usable = max.saturating_sub(top + title_row + padding) + row_gap;
+                (usable / row_pitch).max(1) as usize
+            })
+            .unwrap_or(usize::MAX)
+            .min(rows);
+        let width = padding * 2 + columns as u32 * item_size + (columns as u32 - 1) * spacing;
+        let height = top
+            + visible_rows as u32 * item_size
+            + (visible_rows as u32 - 1) * row_gap
+            + title_row
+            + padding;
+        Self {
+            count,
+            columns,
+            width,
+            height,
+            item_size,
+            spacing,
+            row_pitch,
+            top,
+            visible_rows,
+            first_row: 0,
+        }
+    }
+
+    /// Top-left corner of tile `idx`, or `None` when its row is scrolled
+    /// out of view.
+    pub fn tile_position(&self, idx: usize) -> Option<(i32, i32)> {
+        let row = idx / self.columns;
+        if idx >= self.count || row < self.first_row || row >= self.first_row + self.visible_rows {
+            return None;
+        }
+        let y = self.top as i32 + (row - self.first_row) as i32 * self.row_pitch as i32;
+        Some((self.tile_x(idx), y))
+    }
+
+    pub fn hit_test(&self, (x, y): (f64, f64)) -> Option<usize> {
+        if self.count == 0 || y < self.top as f64 {
+            return None;
+        }
+        let shown_row = ((y - self.top as f64) / self.row_pitch as f64).floor() as usize;
+        let row_top = self.top as f64 + (shown_row as u32 * self.row_pitch) as f64;
+        let row = self.first_row + shown_row;
+        if shown_row >= self.visible_rows
+            || row >= self.rows()
+            || y > row_top + self.item_size as f64
+        {
+            return None;
+        }
+        let pitch = (self.item_size + self.spacing) as f64;
+        let column = ((x - self.row_start(row) as f64) / pitch).floor();
+        if column < 0.0 || column as usize >= self.tiles_in_row(row) {
+            return None;
+        }
+        let tile_x = self.row_start(row) as f64 + column * pitch;
+        if x > tile_x + self.item_size as f64 {
+            return None;
+        }
+        Some(row * self.columns + column as usize)
+    }
+
+    /// The tile an arrow key moves to from `idx`. Left and right wrap
+    /// around the ends; up and down go to the closest tile of the next row
+    /// and return `None` past the first or last row.
+    pub fn neighbor(&self, idx: usize, step: Step) -> Option<usize> {
+        if self.count == 0 {
+            return None;
+        }
+        let row = idx / self.columns;
+        let target_row = match step {
+            Step::Left => return Some((idx + self.count - 1) % self.count),
+            Step::Right => return Some((idx + 1) % self.count),
+            Step::Up => row.checked_sub(1)?,
+            Step::Down => Some(row + 1).filter(|&row| row < self.rows())?,
+        };
+        let x = self.tile_x(idx);
+        let first = target_row * self.columns;
+        (first..first + self.tiles_in_row(target_row))
+            .min_by_key(|&other| (self.tile_x(other) - x).abs())
+    }
+
+    /// Scrolls by whole rows so tile `idx` is shown.
+    pub fn scroll_to(&mut self, idx: usize) {
+        let row = idx / self.columns;
+        if row < self.first_row {
+            self.first_row = row;
+        } else if row >= self.first_row + self.visible_rows {
+            self.first_row = row + 1 - self.visible_rows;
+        }
+    }
+
+    /// Left edge of tile `idx`, whether or not its row is shown.
+    fn tile_x(&self, idx: usize) -> i32 {
+        let column = idx % self.columns;
+        self.row_start(idx / self.columns) + column as i32 * (self.item_size + self.spacing) as i32
+    }
+
+    fn rows(&self) -> usize {
+        self.count.div_ceil(self.columns)
+    }
+
+    fn tiles_in_row(&self, row: usize) -> usize {
+        self.count
+            .saturating_sub(row * self.columns)
+            .min(self.columns)
+    }
+
+    /// Left edge of the first tile in `row`, which is centered in the panel.
+    fn row_start(&self, row: usize) -> i32 {
+        let tiles = self.tiles_in_row(row) as i32;
+        let row_width = tiles * self.item_size as i32 + (tiles - 1).max(0) * self.spacing as i32;
+        ((self.width as i32 - row_width) / 2).max(0)
+    }
+}
+
+/// One full-width row per window with its icon, title and app name. Rows
+/// that do not fit the output height scroll.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ListLayout {
+    count: usize,
+    pub width: u32,
+    pub height: u32,
+    pub row_height: u32,
+    pub icon_size: u32,
+    /// Space around the icon and text inside a row.
+    pub row_padding: u32,
+    top: u32,
+    padding: u32,
+    visible: usize,
+    first: usize,
+}
+
+impl ListLayout {
+    pub fn new(
+        count: usize,
+        top: u32,
+        max_width: Option<u32>,
+        max_height: Option<u32>,
+        config: &AppConfig,
+    ) -> Self {
+        let padding = config.panel_padding;
+        let icon_size = config.text_row_height() * 2;
+        let row_padding = config.highlight_padding / 4;
+        let row_height = icon_size + row_padding * 2;
+        let width = max_width.map_or(config.list_width, |max| config.list_width.min(max));
+        let visible = max_height
+            .map(|max| (max.saturating_sub(top + padding) / row_height).max(1) as usize)
+            .unwrap_or(usize::MAX)
+            .min(count)
+            .max(1);
+        Self {
+            count,
+            width,
+            height: top + visible as u32 * row_height + padding,
+            row_height,
+            icon_size,
+            row_padding,
+            top,
+            padding,
+            visible,
+            first: 0,
+        }
+    }
+
+    pub fn row_width(&self) -> u32 {
+        self.width.saturating_sub(self.padding * 2)
+    }
+
+    pub fn row_position(&self, idx: usize) -> Option<(i32, i32)> {
+        if idx >= self.count || idx < self.first || idx >= self.first + self.visible {
+            return None;
+        }
+        let y = self.top + (idx - self.first) as u32 * self.row_height;
+        Some((self.padding as i32, y as i32))
+    }
+
+    pub fn hit_test(&self, (x, y): (f64, f64)) -> Option<usize> {
+        if x < self.padding as f64
+            || x > (self.padding + self.row_width()) as f64
+            || y < self.top as f64
+        {
+            return None;
+        }
+        let row = ((y - self.top as f64) / self.row_height as f64).floor() as usize;
+        let idx = self.first + row;
+        (row < self.visible && idx < self.count).then_some(idx)
+    }
+
+    /// Up and down move one row and stop at the ends; left and right wrap
+    /// around like in the grid.
+    pub fn neighbor(&self, idx: usize, step: Step) -> Option<usize> {
+        if self.count == 0 {
+            return None;
+        }
+        match step {
+            Step::Left => Some((idx + self.count - 1) % self.count),
+            Step::Right => Some((idx + 1) % self.count),
+            Step::Up => idx.checked_sub(1),
+            Step::Down => Some(idx + 1).filter(|&idx| idx < self.count),
+        }
+    }
+
+    pub fn scroll_to(&mut self, idx: usize) {
+        if idx < self.first {
+            self.first = idx;
+        } else if idx >= self.first + self.visible {
+            self.first = idx + 1 - self.visible;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_wrap_to_the_output_and_arrows_follow_the_grid() {
+        let config = AppConfig {
+            icon_size: 60,
+            highlight_padding: 20,
+            icon_spacing: 20,
+            panel_padding: 10,
+            ..AppConfig::default()
+        };
+        // 100px tiles with 20px gaps: four fit in 480px, not five.
+        let single = GridLayout::new(3, 10, Some(480), None, &config);
+        assert_eq!((single.columns, single.rows()), (3, 1));
+        assert_eq!(single.width, 10 * 2 + 3 * 100 + 2 * 20);
+
+        let grid = GridLayout::new(6, 10, Some(480), None, &config);
+        assert_eq!((grid.columns, grid.rows()), (4, 2));
+        assert_eq!(grid.width, 10 * 2 + 4 * 100 + 3 * 20);
+        // The second row holds two tiles, centered.
+        assert_eq!(grid.tile_position(0), Some((10, 10)));
+        assert_eq!(
+            grid.tile_position(4),
+            Some((130, 10 + grid.row_pitch as i32))
+        );
+        assert_eq!(grid.tile_position(6), None);
+
+        assert_eq!(
+            grid.hit_test((135.0, 15.0 + grid.row_pitch as f64)),
+            Some(4)
+        );
+        assert_eq!(grid.hit_test((115.0, 15.0)), None);
+        assert_eq!(grid.hit_test((15.0, 15.0 + grid.row_pitch as f64)), None);
+
+        assert_eq!(grid.neighbor(1, Step::Down), Some(4));
+        assert_eq!(grid.neighbor(3, Step::Down), Some(5));
+        assert_eq!(grid.neighbor(5, Step::Up), Some(2));
+        assert_eq!(grid.neighbor(2, Step::Up), None);
+        assert_eq!(grid.neighbor(4, Step::Down), None);
+        assert_eq!(grid.neighbor(5, Step::Right), Some(0));
+        assert_eq!(grid.neighbor(0, Step::Left), Some(5));
+
+        // An output with room for one row scrolls to the selected tile.
+        let mut short = GridLayout::new(6, 10, Some(480), Some(200), &config);
+        assert_eq!(short.visible_rows, 1);
+        assert_eq!(short.height, grid.height - grid.row_pitch);
+        assert_eq!(short.tile_position(4), None);
+        assert_eq!(short.neighbor(1, Step::Down), Some(4));
+        short.scroll_to(4);
+        assert_eq!(short.tile_position(0), None);
+        assert_eq!(short.tile_position(4), Some((130, 10)));
+        assert_eq!(short.hit_test((135.0, 15.0)), Some(4));
+        assert_eq!(short.hit_test((135.0, 15.0 + grid.row_pitch as f64)), None);
+
+        let mut list = ListLayout::new(10, 10, Some(2000), Some(200), &config);
+        let rows = list.visible;
+        assert!(rows < 10);
+        assert_eq!(list.width, config.list_width);
+        assert_eq!(list.row_position(rows), None);
+        list.scroll_to(9);
+        assert_eq!(
+            list.row_position(9),
+            Some((10, 10 + (rows as i32 - 1) * list.row_height as i32))
+        );
+        assert_eq!(list.hit_test((20.0, 11.0)), Some(10 - rows));
+        assert_eq!(list.neighbor(9, Step::Down), None);
+    }
+}
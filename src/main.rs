@@ -1,55 +1,165 @@
 mod backend;
+mod color;
 mod config;
 mod daemon;
 mod icon;
+mod ipc;
+mod keys;
+mod layout;
 mod mru;
+mod rules;
+mod search;
 mod switcher;
+mod text;
+mod theme;
+mod thumbnail;
 mod types;
 
 use anyhow::Result;
+use std::path::Path;
 
-use crate::daemon::{run_daemon, send_show, send_show_prev};
-use crate::types::BackendKind;
+use crate::daemon::{
+    run_daemon, send_reload, send_show, send_show_app, send_show_app_prev, send_show_prev,
+};
+use crate::types::{BackendKind, WindowFilter};
 
 fn parse_backend_required(args: &[String]) -> Result<BackendKind> {
     let mut iter = args.iter();
     while let Some(arg) = iter.next() {
-        if arg == "--backend" {
-            if let Some(value) = iter.next() {
-                return match value.as_str() {
-                    "niri" => Ok(BackendKind::Niri),
-                    "sway" => Ok(BackendKind::Sway),
-                    "hyprland" => Ok(BackendKind::Hyprland),
-                    "kwin" => Ok(BackendKind::Kwin),
-                    "gnome" => Ok(BackendKind::Gnome),
-                    _ => Err(anyhow::anyhow!("unknown backend: {value}")),
-                };
-            }
+        if arg == "--backend"
+            && let Some(value) = iter.next()
+        {
+            return match value.as_str() {
+                "niri" => Ok(BackendKind::Niri),
+                "sway" => Ok(BackendKind::Sway),
+                "hyprland" => Ok(BackendKind::Hyprland),
+                "kwin" => Ok(BackendKind::Kwin),
+                "gnome" => Ok(BackendKind::Gnome),
+                "foreign-toplevel" | "wlr" => Ok(BackendKind::ForeignToplevel),
+                _ => Err(anyhow::anyhow!("unknown backend: {value}")),
+            };
         }
     }
-    Err(anyhow::anyhow!("missing --backend (niri|hyprland)"))
+    Err(anyhow::anyhow!("missing --backend (niri|hyprland|sway|kwin|gnome|foreign-toplevel)"))
 }
 
-fn main() -> Result<()> {
-    config::init();
+/// The `--filter` given for this invocation, if any.
+fn parse_filter(args: &[String]) -> Result<Option<WindowFilter>> {
+    let Some(idx) = args.iter().position(|arg| arg == "--filter") else {
+        return Ok(None);
+    };
+    let value = args
+        .get(idx + 1)
+        .ok_or_else(|| anyhow::anyhow!("--filter needs a value"))?;
+    WindowFilter::from_name(value).map(Some).ok_or_else(|| {
+        anyhow::anyhow!("unknown filter: {value} (all|current-workspace|current-output)")
+    })
+}
+
+/// `witcher check-config [path]`: reports every problem in the config
+/// file and exits with status 1 when there are any.
+fn check_config(path: Option<&String>) -> Result<()> {
+    let loaded = match path {
+        Some(path) => config::load_from(Path::new(path))?,
+        None => config::load()?,
+    };
+    let Some(path) = &loaded.path else {
+        println!("no config file, using the defaults");
+        return Ok(());
+    };
+    if loaded.diagnostics.is_empty() {
+        println!("{}: ok", path.display());
+        return Ok(());
+    }
+    for diagnostic in &loaded.diagnostics {
+        eprintln!("{}:{diagnostic}", path.display());
+    }
+    std::process::exit(1);
+}
 
+/// `witcher print-config`: prints the config in effect as TOML, with the
+/// settings that came from the file marked.
+fn print_config() -> Result<()> {
+    let loaded = config::load()?;
+    for error in loaded.errors() {
+        eprintln!("witcher: {error}");
+    }
+    match &loaded.path {
+        Some(path) => println!("# {}", path.display()),
+        None => println!("# no config file, all defaults"),
+    }
+    let mut current = "";
+    for (section, key, value) in loaded.config.entries() {
+        if section != current {
+            println!("\n[{section}]");
+            current = section;
+        }
+        let setting = format!("{section}.{key}");
+        if loaded.set_keys.contains_key(&setting) {
+            println!("{key} = {value}  # from file");
+        } else if loaded.theme_keys.contains(&setting) {
+            println!("{key} = {value}  # from theme");
+        } else {
+            println!("{key} = {value}");
+        }
+    }
+    for rule in &loaded.config.rules {
+        println!("\n[[rules]]");
+        for (key, value) in rule.entries() {
+            println!("{key} = {value}");
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
     let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("check-config") => return check_config(args.get(1)),
+        Some("print-config") => return print_config(),
+        _ => {}
+    }
+    let filter = parse_filter(&args)?;
     if args.iter().any(|arg| arg == "--cycle-next") {
-        send_show()?;
+        send_show(filter)?;
         return Ok(());
     }
     if args.iter().any(|arg| arg == "--cycle-prev") {
-        send_show_prev()?;
+        send_show_prev(filter)?;
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg == "--cycle-app-next") {
+        send_show_app(filter)?;
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg == "--cycle-app-prev") {
+        send_show_app_prev(filter)?;
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg == "--reload") {
+        send_reload()?;
+        return Ok(());
+    }
+    if let Some(idx) = args.iter().position(|arg| arg == "--ipc") {
+        let request = args
+            .get(idx + 1)
+            .ok_or_else(|| anyhow::anyhow!("--ipc needs a JSON request"))?;
+        let reply = ipc::send_raw(request)?;
+        println!("{}", serde_json::to_string(&reply)?);
+        if !reply.ok {
+            std::process::exit(1);
+        }
         return Ok(());
     }
     if args.iter().any(|arg| arg == "--daemon") {
         let backend = parse_backend_required(&args)?;
+        config::init();
         run_daemon(backend)?;
         return Ok(());
     }
 
     eprintln!(
-        "Usage: witcher --daemon --backend <name>\n       witcher --cycle-next\n       witcher --cycle-prev\nSupported backends: niri, hyprland"
+        "Usage: witcher --daemon --backend <name>\n       witcher --cycle-next [--filter <filter>]\n       witcher --cycle-prev [--filter <filter>]\n       witcher --cycle-app-next [--filter <filter>]\n       witcher --cycle-app-prev [--filter <filter>]\n       witcher --reload\n       witcher check-config [path]\n       witcher print-config\n       witcher --ipc <json-request>\nSupported backends: niri, hyprland, sway, kwin, gnome, foreign-toplevel\nFilters: all, current-workspace, current-output"
     );
     Ok(())
 }
@@ -1,5 +1,11 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
 use crate::types::WindowEntry;
 
+const MAX_ENTRIES: usize = 256;
+
 #[derive(Default)]
 pub struct MruState {
     order: Vec<u64>,
@@ -9,23 +15,62 @@ impl MruState {
     pub fn update_on_focus(&mut self, id: u64) {
         self.order.retain(|&existing| existing != id);
         self.order.insert(0, id);
-        if self.order.len() > 256 {
-            self.order.truncate(256);
+        self.order.truncate(MAX_ENTRIES);
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.order.retain(|&existing| existing != id);
+    }
+
+    /// Forgets ids that are not in `existing`, e.g. windows closed while the
+    /// daemon was not running.
+    pub fn retain_existing(&mut self, existing: &HashSet<u64>) {
+        self.order.retain(|id| existing.contains(id));
+    }
+
+    /// Windows in MRU order, with pinned windows right after the focused
+    /// one. With `group_by_app`, each app becomes one entry for its most
+    /// recent window, holding the rest in `grouped`, and apps are ordered by
+    /// that window.
+    pub fn order_windows(&self, windows: Vec<WindowEntry>, group_by_app: bool) -> Vec<WindowEntry> {
+        let mut windows = self.order_by_key(windows, |window| (window.id, window.is_focused));
+        windows.sort_by_key(|window| !window.is_focused && !window.pinned);
+        if !group_by_app {
+            return windows;
         }
+        let mut groups: Vec<WindowEntry> = Vec::new();
+        let mut group_index = HashMap::<String, usize>::new();
+        for window in windows {
+            match group_index.get(&window.app_id) {
+                Some(&idx) => groups[idx].grouped.push(window),
+                None => {
+                    group_index.insert(window.app_id.clone(), groups.len());
+                    groups.push(window);
+                }
+            }
+        }
+        groups
     }
 
-    pub fn order_windows(&self, windows: Vec<WindowEntry>) -> Vec<WindowEntry> {
-        let focused = windows.iter().find(|w| w.is_focused).map(|w| w.id);
-        let mut order_index = std::collections::HashMap::new();
+    /// Sorts anything window-like given its id and whether it is focused:
+    /// the focused window first, then most recently used.
+    pub fn order_by_key<T>(&self, windows: Vec<T>, key: impl Fn(&T) -> (u64, bool)) -> Vec<T> {
+        let focused = windows
+            .iter()
+            .map(&key)
+            .find(|(_, is_focused)| *is_focused)
+            .map(|(id, _)| id);
+        let mut order_index = HashMap::new();
         for (idx, id) in self.order.iter().enumerate() {
             order_index.insert(*id, idx);
         }
 
         let mut ranked = Vec::with_capacity(windows.len());
         for (idx, window) in windows.into_iter().enumerate() {
-            let rank = if Some(window.id) == focused {
+            let (id, _) = key(&window);
+            let rank = if Some(id) == focused {
                 0usize
-            } else if let Some(mru_idx) = order_index.get(&window.id) {
+            } else if let Some(mru_idx) = order_index.get(&id) {
                 1 + *mru_idx
             } else {
                 1 + order_index.len() + idx
@@ -33,7 +78,143 @@ impl MruState {
             ranked.push((rank, idx, window));
         }
 
-        ranked.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        ranked.sort_by_key(|(rank, idx, _)| (*rank, *idx));
         ranked.into_iter().map(|(_, _, window)| window).collect()
     }
 }
+
+/// MRU order saved in `$XDG_STATE_HOME/witcher/mru.json`, one list per
+/// compositor instance.
+pub struct MruStore {
+    path: PathBuf,
+    instance: String,
+}
+
+impl MruStore {
+    pub fn new(instance: String) -> Option<Self> {
+        Some(Self {
+            path: state_path()?,
+            instance,
+        })
+    }
+
+    pub fn load(&self) -> MruState {
+        let mut order = read_instances(&self.path)
+            .remove(&self.instance)
+            .unwrap_or_default();
+        order.truncate(MAX_ENTRIES);
+        MruState { order }
+    }
+
+    pub fn save(&self, mru: &MruState) -> Result<()> {
+        let mut instances = read_instances(&self.path);
+        instances.insert(self.instance.clone(), mru.order.clone());
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("create {}", parent.display()))?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        let text = serde_json::to_string(&instances).context("serialize mru")?;
+        std::fs::write(&tmp_path, text)
+            .with_context(|| format!("write {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("replace {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+fn read_instances(path: &PathBuf) -> HashMap<String, Vec<u64>> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn state_path() -> Option<PathBuf> {
+    if let Some(state_home) = std::env::var_os("XDG_STATE_HOME") {
+        return Some(PathBuf::from(state_home).join("witcher").join("mru.json"));
+    }
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .map(|home| home.join(".local").join("state").join("witcher").join("mru.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_keeps_one_order_per_instance() {
+        let dir = std::env::temp_dir().join(format!("witcher-mru-{}", std::process::id()));
+        let path = dir.join("mru.json");
+        let store = |instance: &str| MruStore {
+            path: path.clone(),
+            instance: instance.to_string(),
+        };
+
+        let mut mru = MruState::default();
+        mru.update_on_focus(1);
+        mru.update_on_focus(2);
+        store("niri:/run/a").save(&mru).unwrap();
+        let mut other = MruState::default();
+        other.update_on_focus(9);
+        store("niri:/run/b").save(&other).unwrap();
+
+        let mut loaded = store("niri:/run/a").load();
+        assert_eq!(loaded.order, vec![2, 1]);
+        loaded.retain_existing(&HashSet::from([1]));
+        assert_eq!(loaded.order, vec![1]);
+        assert_eq!(store("niri:/run/b").load().order, vec![9]);
+        assert!(store("sway:/run/c").load().order.is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn grouping_keeps_one_entry_per_app_in_mru_order() {
+        let icon = std::sync::Arc::new(tiny_skia::Pixmap::new(1, 1).unwrap());
+        let window = |id: u64, app_id: &str| WindowEntry {
+            id,
+            is_focused: id == 4,
+            app_id: app_id.to_string(),
+            app_name: None,
+            title: None,
+            icon: icon.clone(),
+            pinned: false,
+            grouped: Vec::new(),
+        };
+        let mut mru = MruState::default();
+        for id in [1, 2, 3, 5] {
+            mru.update_on_focus(id);
+        }
+        let windows = vec![
+            window(1, "foot"),
+            window(2, "firefox"),
+            window(3, "foot"),
+            window(4, "code"),
+            window(5, "foot"),
+        ];
+
+        let groups = mru.order_windows(windows, true);
+        let summary = groups
+            .iter()
+            .map(|group| {
+                let ids = group
+                    .ungroup()
+                    .iter()
+                    .map(|window| window.id)
+                    .collect::<Vec<_>>();
+                (group.app_id.as_str(), ids)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("code", vec![4]),
+                ("foot", vec![5, 3, 1]),
+                ("firefox", vec![2])
+            ]
+        );
+        assert_eq!(groups[1].window_count(), 3);
+    }
+}
@@ -0,0 +1,192 @@
+use regex::Regex;
+
+use crate::backend::BackendWindow;
+use crate::config::parse_bool;
+
+/// A `[[rules]]` entry: which windows it matches and what it does to them.
+/// Every match key that is set has to match.
+#[derive(Clone, Debug, Default)]
+pub struct Rule {
+    pub app_id: Option<String>,
+    /// Searched for anywhere in the title, so anchor it to match the whole.
+    pub title: Option<Regex>,
+    pub pid: Option<i64>,
+    pub process_name: Option<String>,
+    pub actions: Actions,
+}
+
+/// What rules do to a window. When several rules match, all of them apply
+/// and later rules win for `icon` and `label`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Actions {
+    pub hide: bool,
+    pub pin_first: bool,
+    /// Icon name from the icon theme, or an absolute path to an image.
+    pub icon: Option<String>,
+    /// Shown instead of the window title.
+    pub label: Option<String>,
+}
+
+impl Rule {
+    pub fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "app_id" => self.app_id = Some(value.to_string()),
+            "title" => {
+                let regex = Regex::new(value).map_err(|err| {
+                    // Syntax errors draw the pattern over several lines and
+                    // end with the reason.
+                    let err = err.to_string();
+                    let reason = err.lines().last().unwrap_or_default();
+                    let reason = reason.strip_prefix("error: ").unwrap_or(reason);
+                    format!("invalid value for `{key}`: {reason}")
+                })?;
+                self.title = Some(regex);
+            }
+            "pid" => {
+                let pid = value
+                    .parse()
+                    .map_err(|_| format!("invalid value for `{key}`: expected a process id"))?;
+                self.pid = Some(pid);
+            }
+            "process_name" => self.process_name = Some(value.to_string()),
+            "hide" => self.actions.hide = parse_bool(key, value)?,
+            "pin_first" => self.actions.pin_first = parse_bool(key, value)?,
+            "icon" => self.actions.icon = Some(value.to_string()),
+            "label" => self.actions.label = Some(value.to_string()),
+            _ => return Err(format!("unknown key `{key}` in [[rules]]")),
+        }
+        Ok(())
+    }
+
+    /// Rejects rules that would match every window or do nothing.
+    pub fn check(&self) -> Result<(), String> {
+        if self.app_id.is_none()
+            && self.title.is_none()
+            && self.pid.is_none()
+            && self.process_name.is_none()
+        {
+            return Err(
+                "a rule needs at least one of `app_id`, `title`, `pid` or `process_name`"
+                    .to_string(),
+            );
+        }
+        if self.actions == Actions::default() {
+            return Err(
+                "a rule needs at least one of `hide`, `pin_first`, `icon` or `label`".to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// `app_id` is the one the switcher shows, which falls back to another
+    /// window of the same process or the process name when the compositor
+    /// reports none.
+    pub fn matches(&self, window: &BackendWindow, app_id: &str) -> bool {
+        self.app_id.as_ref().is_none_or(|id| id == app_id)
+            && self.title.as_ref().is_none_or(|regex| {
+                window
+                    .title
+                    .as_deref()
+                    .is_some_and(|title| regex.is_match(title))
+            })
+            && self.pid.is_none_or(|pid| window.pid == Some(pid))
+            && self
+                .process_name
+                .as_ref()
+                .is_none_or(|name| window.process_name.as_ref() == Some(name))
+    }
+
+    /// The rule's keys with their values, in the order the README lists them.
+    pub fn entries(&self) -> Vec<(&'static str, toml::Value)> {
+        let text = |value: &str| toml::Value::String(value.to_string());
+        let mut entries = Vec::new();
+        if let Some(app_id) = &self.app_id {
+            entries.push(("app_id", text(app_id)));
+        }
+        if let Some(title) = &self.title {
+            entries.push(("title", text(title.as_str())));
+        }
+        if let Some(pid) = self.pid {
+            entries.push(("pid", toml::Value::Integer(pid)));
+        }
+        if let Some(name) = &self.process_name {
+            entries.push(("process_name", text(name)));
+        }
+        if self.actions.hide {
+            entries.push(("hide", toml::Value::Boolean(true)));
+        }
+        if self.actions.pin_first {
+            entries.push(("pin_first", toml::Value::Boolean(true)));
+        }
+        if let Some(icon) = &self.actions.icon {
+            entries.push(("icon", text(icon)));
+        }
+        if let Some(label) = &self.actions.label {
+            entries.push(("label", text(label)));
+        }
+        entries
+    }
+}
+
+/// The combined actions of the rules matching `window`.
+pub fn actions_for(rules: &[Rule], window: &BackendWindow, app_id: &str) -> Actions {
+    let mut actions = Actions::default();
+    for rule in rules.iter().filter(|rule| rule.matches(window, app_id)) {
+        actions.hide |= rule.actions.hide;
+        actions.pin_first |= rule.actions.pin_first;
+        if rule.actions.icon.is_some() {
+            actions.icon = rule.actions.icon.clone();
+        }
+        if rule.actions.label.is_some() {
+            actions.label = rule.actions.label.clone();
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(keys: &[(&str, &str)]) -> Rule {
+        let mut rule = Rule::default();
+        for (key, value) in keys {
+            rule.apply(key, value).unwrap();
+        }
+        rule
+    }
+
+    #[test]
+    fn matching_rules_combine_and_later_ones_win() {
+        let window = BackendWindow {
+            id: 1,
+            title: Some("Picture-in-Picture".to_string()),
+            app_id: Some("firefox".to_string()),
+            pid: Some(42),
+            process_name: Some("firefox-bin".to_string()),
+            is_focused: false,
+            workspace: None,
+            output: None,
+        };
+        let rules = [
+            rule(&[("app_id", "firefox"), ("icon", "web-browser")]),
+            rule(&[("title", "^Picture-in-Picture$"), ("hide", "true")]),
+            rule(&[("process_name", "firefox-bin"), ("icon", "/tmp/fox.png")]),
+            rule(&[("pid", "42"), ("app_id", "chromium"), ("pin_first", "true")]),
+        ];
+        assert_eq!(
+            actions_for(&rules, &window, "firefox"),
+            Actions {
+                hide: true,
+                icon: Some("/tmp/fox.png".to_string()),
+                ..Actions::default()
+            }
+        );
+
+        assert!(rule(&[("hide", "true")]).check().is_err());
+        assert!(rule(&[("app_id", "firefox")]).check().is_err());
+        assert!(Rule::default().apply("title", "(").is_err());
+        assert!(Rule::default().apply("pid", "firefox").is_err());
+        assert!(Rule::default().apply("hidden", "true").is_err());
+    }
+}
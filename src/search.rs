@@ -0,0 +1,111 @@
+use crate::types::WindowEntry;
+
+const MATCH: i32 = 1;
+const CONSECUTIVE: i32 = 5;
+const WORD_START: i32 = 8;
+const MAX_GAP_PENALTY: usize = 3;
+
+/// Scores how well `query` matches `text` as a case-insensitive
+/// subsequence, or `None` when it does not match. Runs of consecutive
+/// characters and matches at word starts score higher, gaps lower.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text = text.chars().flat_map(char::to_lowercase).collect::<Vec<_>>();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev = None;
+    for wanted in query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+    {
+        let idx = (pos..text.len()).find(|&idx| text[idx] == wanted)?;
+        score += MATCH;
+        if prev.is_some_and(|prev| prev + 1 == idx) {
+            score += CONSECUTIVE;
+        }
+        if idx == 0 || !text[idx - 1].is_alphanumeric() {
+            score += WORD_START;
+        }
+        if prev.is_some() {
+            score -= (idx - pos).min(MAX_GAP_PENALTY) as i32;
+        }
+        prev = Some(idx);
+        pos = idx + 1;
+    }
+    Some(score)
+}
+
+/// Best score of `query` against the window's app id, desktop name and
+/// title.
+pub fn window_score(query: &str, window: &WindowEntry) -> Option<i32> {
+    [
+        Some(window.app_id.as_str()),
+        window.app_name.as_deref(),
+        window.title.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|text| fuzzy_score(query, text))
+    .max()
+}
+
+/// Windows matching `query`, best match first. Equal scores keep their MRU
+/// order. Tiles grouping an app's windows are searched window by window, so
+/// a match selects that window rather than the app's most recent one.
+pub fn filter_windows(query: &str, windows: &[WindowEntry]) -> Vec<WindowEntry> {
+    let mut matches = windows
+        .iter()
+        .flat_map(WindowEntry::ungroup)
+        .filter_map(|window| window_score(query, &window).map(|score| (score, window)))
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    matches.into_iter().map(|(_, window)| window).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_starts_and_runs_rank_first() {
+        assert_eq!(fuzzy_score("fx", "Terminal"), None);
+        assert!(fuzzy_score("", "anything").is_some());
+        assert!(fuzzy_score("FiFo", "firefox").is_some());
+
+        let prefix = fuzzy_score("fire", "Firefox").unwrap();
+        let scattered = fuzzy_score("fire", "foot: file-reader").unwrap();
+        assert!(prefix > scattered);
+        let word_start = fuzzy_score("vsc", "Visual Studio Code").unwrap();
+        let inside = fuzzy_score("vsc", "devscripts").unwrap();
+        assert!(word_start > inside);
+    }
+
+    #[test]
+    fn grouped_windows_are_searched_one_by_one() {
+        let icon = std::sync::Arc::new(tiny_skia::Pixmap::new(1, 1).unwrap());
+        let window = |id: u64, title: &str| WindowEntry {
+            id,
+            is_focused: false,
+            app_id: "firefox".to_string(),
+            app_name: None,
+            title: Some(title.to_string()),
+            icon: icon.clone(),
+            pinned: false,
+            grouped: Vec::new(),
+        };
+        let group = WindowEntry {
+            grouped: vec![window(2, "Inbox - Mail"), window(3, "Rust docs")],
+            ..window(1, "News")
+        };
+
+        let ids = |query| {
+            filter_windows(query, std::slice::from_ref(&group))
+                .iter()
+                .map(|window| (window.id, window.window_count()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("inbox"), [(2, 1)]);
+        assert_eq!(ids("fox"), [(1, 1), (2, 1), (3, 1)]);
+        assert!(ids("calendar").is_empty());
+    }
+}
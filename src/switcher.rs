@@ -26,7 +26,12 @@ use std::collections::HashMap;
 use std::io::Read;
 use std::os::fd::{AsFd, AsRawFd};
 use std::os::unix::net::UnixStream;
-use tiny_skia::{BlendMode, Color, Paint, PathBuilder, PixmapMut, PixmapPaint, Stroke, Transform};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tiny_skia::{
+    BlendMode, Color, FilterQuality, Paint, PathBuilder, Pixmap, PixmapMut, PixmapPaint, Stroke,
+    Transform,
+};
 use wayland_client::{
     Connection, QueueHandle, delegate_noop,
     globals::{GlobalList, registry_queue_init},
@@ -37,43 +42,93 @@ use wayland_protocols::ext::background_effect::v1::client::{
     ext_background_effect_surface_v1::ExtBackgroundEffectSurfaceV1,
 };
 
-use crate::backend::{backend_windows, focus_window, focused_output_info};
-use crate::config::{
-    app_config, panel_border_alpha, panel_opacity_alpha, selected_indicator_alpha,
-    selected_indicator_border_alpha,
-};
+use crate::backend::{Backend, SharedBackend, filter_by_place};
+use crate::config::{AppConfig, LayoutMode, TitleMode, opacity_alpha};
 use crate::icon::IconCache;
+use crate::keys::KeyAction;
+use crate::layout::{Layout, ListLayout, Step};
 use crate::mru::MruState;
-use crate::types::{BackendKind, WindowEntry};
+use crate::rules::{Rule, actions_for};
+use crate::search::filter_windows;
+use crate::text::{TextStyle, draw_text, draw_text_left};
+use crate::thumbnail::{ThumbnailHandler, ThumbnailTarget, Thumbnails, delegate_thumbnails};
+use crate::types::{WindowEntry, WindowFilter};
 
 pub enum SwitcherControl {
     CycleNext,
     CyclePrev,
+    /// Narrows an open switcher to the focused app's windows, then cycles.
+    CycleAppNext,
+    CycleAppPrev,
+    Hide,
+    Select(u64),
+}
+
+/// Which windows the switcher opens with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    All,
+    /// Only windows sharing the focused window's app id.
+    FocusedApp,
 }
 
 pub fn run_switcher(
-    backend: BackendKind,
+    backend: &SharedBackend,
     icon_cache: &mut IconCache,
-    mru: &mut MruState,
+    mru: &Mutex<MruState>,
+    scope: Scope,
+    filter: WindowFilter,
     control_rx: std::sync::mpsc::Receiver<SwitcherControl>,
     mut wake_read: UnixStream,
 ) -> Result<Option<u64>> {
-    let mut windows = load_windows(backend, icon_cache).context("load windows via backend")?;
+    // The snapshot the icons were loaded for, so a reload while the
+    // switcher is open changes neither.
+    let config = icon_cache.config().clone();
+    let mut windows = load_windows(
+        backend.lock().unwrap().as_mut(),
+        icon_cache,
+        filter,
+        &config.rules,
+    )
+    .context("load windows via backend")?;
     if windows.is_empty() {
         return Ok(None);
     }
 
+    if scope == Scope::FocusedApp {
+        windows = same_app_as_focused(windows);
+    }
+
     let focused_id = windows.iter().find(|w| w.is_focused).map(|w| w.id);
-    if let Some(id) = focused_id {
-        mru.update_on_focus(id);
+    {
+        let mut mru = mru.lock().unwrap();
+        if let Some(id) = focused_id {
+            mru.update_on_focus(id);
+        }
+        windows = mru.order_windows(windows, config.group_by_app && scope == Scope::All);
     }
-    windows = mru.order_windows(windows);
 
     let selected = if windows.len() > 1 { 1 } else { 0 };
-    let config = *app_config();
     let icon_size = config.icon_size;
-    let (desired_width, desired_height) = layout_size(windows.len(), icon_size);
-    let (initial_output_size, initial_scale) = focused_output_info(backend).unwrap_or((None, 1));
+    let (initial_output_size, initial_scale) = backend
+        .lock()
+        .unwrap()
+        .focused_output_info()
+        .unwrap_or((None, 1));
+    let layout = Layout::new(
+        config.layout,
+        windows.len(),
+        config.panel_padding,
+        initial_output_size,
+        &config,
+    );
+    let (desired_width, desired_height) = layout.size();
+    let focused_output = backend
+        .lock()
+        .unwrap()
+        .focused_place()
+        .ok()
+        .and_then(|place| place.output);
 
     let conn = Connection::connect_to_env().context("connect to Wayland")?;
     let (globals, mut event_queue) =
@@ -84,9 +139,20 @@ pub fn run_switcher(
     let layer_shell = LayerShell::bind(&globals, &qh).context("layer shell not available")?;
     let shm = Shm::bind(&globals, &qh).context("wl_shm not available")?;
 
+    // Without an output the compositor picks one, which need not be the
+    // monitor the user is looking at.
+    let output = focused_output
+        .as_deref()
+        .and_then(|name| find_output(&conn, &globals, name));
+    let output_name = focused_output.filter(|_| output.is_some());
     let surface = compositor.create_surface(&qh);
-    let layer =
-        layer_shell.create_layer_surface(&qh, surface, Layer::Overlay, Some("witcher"), None);
+    let layer = layer_shell.create_layer_surface(
+        &qh,
+        surface,
+        Layer::Overlay,
+        Some("witcher"),
+        output.as_ref(),
+    );
     layer.set_anchor(Anchor::TOP | Anchor::LEFT);
     layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
     layer.set_exclusive_zone(-1);
@@ -107,26 +173,57 @@ pub fn run_switcher(
     let subcompositor =
         SubcompositorState::bind(compositor.wl_compositor().clone(), &globals, &qh).ok();
     let panel_shadow = subcompositor.as_ref().map(|subcompositor| {
-        create_panel_shadow(&qh, &compositor, subcompositor, layer.wl_surface(), initial_scale)
-    });
-    let selected_indicator = subcompositor.as_ref().map(|subcompositor| {
-        create_selected_indicator(
-            &globals,
+        create_panel_shadow(
             &qh,
             &compositor,
             subcompositor,
             layer.wl_surface(),
-            icon_size + config.highlight_padding * 2,
             initial_scale,
+            &config,
         )
     });
+    // The list layout draws its selection bar on the panel itself.
+    let selected_indicator = subcompositor
+        .as_ref()
+        .filter(|_| config.layout == LayoutMode::Grid)
+        .map(|subcompositor| {
+            create_selected_indicator(
+                &globals,
+                &qh,
+                &compositor,
+                subcompositor,
+                layer.wl_surface(),
+                icon_size + config.highlight_padding * 2,
+                initial_scale,
+                &config,
+            )
+        });
     layer.commit();
 
     let pool = SlotPool::new((desired_width * desired_height * 4) as usize, &shm)
         .context("create shm pool")?;
+    let thumbnails = if config.thumbnails {
+        let targets = {
+            let backend = backend.lock().unwrap();
+            windows
+                .iter()
+                .flat_map(WindowEntry::ungroup)
+                .map(|window| ThumbnailTarget {
+                    id: window.id,
+                    export_handle: backend.export_handle(window.id),
+                    app_id: window.app_id.clone(),
+                    title: window.title.clone(),
+                })
+                .collect()
+        };
+        let max_size = (icon_size + config.highlight_padding) * initial_scale;
+        Thumbnails::new(&globals, &qh, &shm, targets, max_size)
+    } else {
+        None
+    };
 
     let mut app = Switcher {
-        backend,
+        backend: backend.clone(),
         registry_state: RegistryState::new(&globals),
         seat_state: SeatState::new(&globals, &qh),
         output_state: OutputState::new(&globals, &qh),
@@ -137,16 +234,26 @@ pub fn run_switcher(
         height: desired_height,
         buffer_scale: initial_scale,
         output_logical_size: initial_output_size,
-        _background_effect: background_effect,
+        output_name,
+        layout,
+        compositor,
+        background_effect,
         _subcompositor: subcompositor,
         panel_shadow,
         selected_indicator,
+        thumbnails,
+        thumbnails_changed: false,
         first_configure: true,
         exit: false,
         keyboard: None,
         pointer: None,
         modifiers: Modifiers::default(),
+        all_windows: windows.clone(),
         windows,
+        scope,
+        config,
+        query: String::new(),
+        resize_pending: false,
         selected,
         hovered: None,
         redraw: true,
@@ -172,8 +279,16 @@ pub fn run_switcher(
         if app.exit {
             break;
         }
+        let timeout = app
+            .thumbnails
+            .as_ref()
+            .and_then(|thumbnails| thumbnails.next_refresh())
+            .map(|at| at.saturating_duration_since(Instant::now()));
         let (wayland_ready, wake_ready) =
-            poll_wayland_and_wake(wayland_fd, wake_fd).context("poll inputs")?;
+            poll_wayland_and_wake(wayland_fd, wake_fd, timeout).context("poll inputs")?;
+        if let Some(thumbnails) = app.thumbnails.as_mut() {
+            thumbnails.refresh(&qh);
+        }
         if wake_ready {
             drain_wake_pipe(&mut wake_read);
             drain_controls(&mut app, &qh, &control_rx);
@@ -187,6 +302,9 @@ pub fn run_switcher(
                 .blocking_dispatch(&mut app)
                 .context("dispatch events")?;
         }
+        if std::mem::take(&mut app.thumbnails_changed) && !app.first_configure && !app.exit {
+            app.draw(&qh);
+        }
     }
 
     if app.canceled {
@@ -197,7 +315,7 @@ pub fn run_switcher(
 }
 
 struct Switcher {
-    backend: BackendKind,
+    backend: SharedBackend,
     registry_state: RegistryState,
     seat_state: SeatState,
     output_state: OutputState,
@@ -208,16 +326,31 @@ struct Switcher {
     height: u32,
     buffer_scale: u32,
     output_logical_size: Option<(i32, i32)>,
-    _background_effect: Option<BackgroundEffect>,
+    /// The output the panel was placed on, when it could be matched.
+    output_name: Option<String>,
+    layout: Layout,
+    compositor: CompositorState,
+    background_effect: Option<BackgroundEffect>,
     _subcompositor: Option<SubcompositorState>,
     panel_shadow: Option<PanelShadow>,
     selected_indicator: Option<SelectedIndicator>,
+    thumbnails: Option<Thumbnails>,
+    thumbnails_changed: bool,
     first_configure: bool,
     exit: bool,
     keyboard: Option<wl_keyboard::WlKeyboard>,
     pointer: Option<wl_pointer::WlPointer>,
     modifiers: Modifiers,
+    /// Every window, in MRU order.
+    all_windows: Vec<WindowEntry>,
+    /// The windows shown: all of them, or the ones matching `query`.
     windows: Vec<WindowEntry>,
+    scope: Scope,
+    /// The config as of opening, so a reload does not affect an open
+    /// switcher.
+    config: Arc<AppConfig>,
+    query: String,
+    resize_pending: bool,
     selected: usize,
     hovered: Option<usize>,
     redraw: bool,
@@ -227,7 +360,7 @@ struct Switcher {
 
 struct BackgroundEffect {
     _manager: ExtBackgroundEffectManagerV1,
-    _surface: ExtBackgroundEffectSurfaceV1,
+    surface: ExtBackgroundEffectSurfaceV1,
 }
 
 struct SelectedIndicator {
@@ -248,11 +381,31 @@ impl Switcher {
         match msg {
             SwitcherControl::CycleNext => self.cycle(1, qh),
             SwitcherControl::CyclePrev => self.cycle(-1, qh),
+            SwitcherControl::CycleAppNext => self.cycle_app(1, qh),
+            SwitcherControl::CycleAppPrev => self.cycle_app(-1, qh),
+            SwitcherControl::Hide => {
+                self.canceled = true;
+                self.exit = true;
+            }
+            SwitcherControl::Select(id) => {
+                if !self.windows.iter().any(|window| window.id == id) {
+                    self.windows = self
+                        .all_windows
+                        .iter()
+                        .flat_map(WindowEntry::ungroup)
+                        .collect();
+                }
+                if let Some(idx) = self.windows.iter().position(|window| window.id == id) {
+                    self.selected = idx;
+                    self.finalize();
+                }
+            }
         }
     }
 
     fn draw(&mut self, qh: &QueueHandle<Self>) {
-        let config = *app_config();
+        let config = self.config.clone();
+        self.layout.scroll_to(self.selected);
         let buffer_width = self.width * self.buffer_scale;
         let buffer_height = self.height * self.buffer_scale;
         let stride = buffer_width as i32 * 4;
@@ -293,7 +446,7 @@ impl Switcher {
             .expect("create buffer");
 
         {
-            let mut pixmap = PixmapMut::from_bytes(canvas.as_mut(), buffer_width, buffer_height)
+            let mut pixmap = PixmapMut::from_bytes(canvas, buffer_width, buffer_height)
                 .expect("pixmap from buffer");
             pixmap.fill(Color::from_rgba8(0, 0, 0, 0));
 
@@ -306,14 +459,13 @@ impl Switcher {
                 self.height as f32,
                 config.corner_radius,
             );
-            let panel_alpha = panel_opacity_alpha();
+            let panel_alpha = opacity_alpha(config.panel_opacity);
             let mut paint = Paint::default();
-            paint.set_color(Color::from_rgba8(
-                config.panel_border_color.r,
-                config.panel_border_color.g,
-                config.panel_border_color.b,
-                panel_border_alpha(),
-            ));
+            paint.set_color(
+                config
+                    .panel_border_color
+                    .with_alpha(opacity_alpha(config.panel_border_opacity)),
+            );
             pixmap.fill_path(
                 &outer,
                 &paint,
@@ -332,12 +484,13 @@ impl Switcher {
                 inner_height,
                 (config.corner_radius - inset).max(0.0),
             );
-            paint.set_color(Color::from_rgba8(
-                config.panel_background_color.r,
-                config.panel_background_color.g,
-                config.panel_background_color.b,
+            let mut paint = config.panel_background_color.paint(
                 panel_alpha,
-            ));
+                inset,
+                inset,
+                inner_width,
+                inner_height,
+            );
             paint.blend_mode = BlendMode::Source;
             pixmap.fill_path(
                 &inner,
@@ -349,89 +502,173 @@ impl Switcher {
             paint.blend_mode = BlendMode::SourceOver;
 
             let item_size = config.icon_size + config.highlight_padding * 2;
-            let total_width = self.windows.len() as i32 * item_size as i32
-                + (self.windows.len().saturating_sub(1) as i32 * config.icon_spacing as i32);
             let available = self.width as i32 - (config.panel_padding as i32 * 2);
-            let start_x = (config.panel_padding as i32 + ((available - total_width) / 2)).max(0);
-            let y = self.height as i32 / 2 - (config.icon_size / 2) as i32;
-            let hover_y = y - config.highlight_padding as i32;
-            for (idx, window) in self.windows.iter().enumerate() {
-                let item_x = start_x + idx as i32 * (item_size + config.icon_spacing) as i32;
-                let icon_x = item_x + config.highlight_padding as i32;
-                let is_selected = idx == self.selected;
-                let is_hovered = self.hovered == Some(idx);
-                if is_selected || is_hovered {
-                    let highlight = rounded_rect_path(
-                        item_x as f32,
-                        hover_y as f32,
-                        item_size as f32,
-                        item_size as f32,
-                        config.corner_radius * 0.7,
-                    );
-                    if is_selected {
-                        draw_selected_indicator_shadow(
+            let style = TextStyle {
+                family: &config.font_family,
+                size: config.font_size,
+                color: config.font_color,
+                alpha: 255,
+            };
+            match self.layout {
+                Layout::List(list) => {
+                    for (idx, window) in self.windows.iter().enumerate() {
+                        let Some((row_x, row_y)) = list.row_position(idx) else {
+                            continue;
+                        };
+                        draw_list_row(
                             &mut pixmap,
                             transform,
-                            item_x as f32,
-                            hover_y as f32,
-                            item_size as f32,
-                            config.corner_radius * 0.7,
+                            &list,
+                            (row_x as f32, row_y as f32),
+                            window,
+                            idx == self.selected,
+                            self.hovered == Some(idx),
                             &config,
                         );
                     }
-                    if is_selected && !selected_on_child {
-                        let mut paint = Paint::default();
-                        paint.set_color(Color::from_rgba8(
-                            config.selected_indicator_color.r,
-                            config.selected_indicator_color.g,
-                            config.selected_indicator_color.b,
-                            selected_indicator_alpha(),
-                        ));
-                        pixmap.fill_path(
-                            &highlight,
-                            &paint,
-                            tiny_skia::FillRule::Winding,
+                }
+                Layout::Grid(_) => {
+                    for (idx, window) in self.windows.iter().enumerate() {
+                        let Some((item_x, hover_y)) = self.layout.item_position(idx) else {
+                            continue;
+                        };
+                        let is_selected = idx == self.selected;
+                        let is_hovered = self.hovered == Some(idx);
+                        if is_selected || is_hovered {
+                            let highlight = rounded_rect_path(
+                                item_x as f32,
+                                hover_y as f32,
+                                item_size as f32,
+                                item_size as f32,
+                                config.corner_radius * 0.7,
+                            );
+                            if is_selected {
+                                draw_selected_indicator_shadow(
+                                    &mut pixmap,
+                                    transform,
+                                    item_x as f32,
+                                    hover_y as f32,
+                                    item_size as f32,
+                                    config.corner_radius * 0.7,
+                                    &config,
+                                );
+                            }
+                            if is_selected && !selected_on_child {
+                                let paint = config.selected_indicator_color.paint(
+                                    opacity_alpha(config.selected_indicator_opacity),
+                                    item_x as f32,
+                                    hover_y as f32,
+                                    item_size as f32,
+                                    item_size as f32,
+                                );
+                                pixmap.fill_path(
+                                    &highlight,
+                                    &paint,
+                                    tiny_skia::FillRule::Winding,
+                                    transform,
+                                    None,
+                                );
+                            }
+                            if is_hovered || is_selected {
+                                let mut paint = Paint::default();
+                                let color = if is_selected {
+                                    config.selected_indicator_border_color
+                                } else {
+                                    config.hover_border_color
+                                };
+                                let alpha = if is_selected {
+                                    opacity_alpha(config.selected_indicator_border_opacity)
+                                } else {
+                                    panel_alpha
+                                };
+                                paint.set_color(color.with_alpha(alpha));
+                                let stroke = Stroke {
+                                    width: config.indicator_border_width.max(1.0),
+                                    ..Stroke::default()
+                                };
+                                pixmap.stroke_path(&highlight, &paint, &stroke, transform, None);
+                            }
+                        }
+
+                        if is_selected && selected_on_child {
+                            continue;
+                        }
+
+                        let thumbnail = self
+                            .thumbnails
+                            .as_ref()
+                            .and_then(|thumbnails| thumbnails.get(window.id));
+                        draw_tile_content(
+                            &mut pixmap,
                             transform,
-                            None,
+                            item_x as f32,
+                            hover_y as f32,
+                            window,
+                            thumbnail.as_deref(),
+                            &config,
                         );
                     }
-                    if is_hovered || is_selected {
-                        let mut paint = Paint::default();
-                        let color = if is_selected {
-                            config.selected_indicator_border_color
-                        } else {
-                            config.hover_border_color
-                        };
-                        let alpha = if is_selected {
-                            selected_indicator_border_alpha()
-                        } else {
-                            panel_alpha
-                        };
-                        paint.set_color(Color::from_rgba8(color.r, color.g, color.b, alpha));
-                        let mut stroke = Stroke::default();
-                        stroke.width = config.indicator_border_width.max(1.0);
-                        pixmap.stroke_path(&highlight, &paint, &stroke, transform, None);
-                    }
-                }
 
-                if is_selected && selected_on_child {
-                    continue;
+                    let title_height = config.title_row_height() as f32;
+                    match config.title_mode {
+                        TitleMode::None => {}
+                        TitleMode::Selected => {
+                            if let Some(title) = self
+                                .windows
+                                .get(self.selected)
+                                .and_then(|window| window.title.as_deref())
+                                && let Some((_, item_y)) = self.layout.item_position(self.selected)
+                            {
+                                let title_top = (item_y + item_size as i32) as f32;
+                                draw_text(
+                                    &mut pixmap,
+                                    transform,
+                                    title,
+                                    &style,
+                                    self.width as f32 / 2.0,
+                                    title_top,
+                                    title_height,
+                                    available as f32,
+                                );
+                            }
+                        }
+                        TitleMode::All => {
+                            for (idx, window) in self.windows.iter().enumerate() {
+                                let Some(title) = window.title.as_deref() else {
+                                    continue;
+                                };
+                                let Some((item_x, item_y)) = self.layout.item_position(idx) else {
+                                    continue;
+                                };
+                                let title_top = (item_y + item_size as i32) as f32;
+                                draw_text(
+                                    &mut pixmap,
+                                    transform,
+                                    title,
+                                    &style,
+                                    item_x as f32 + item_size as f32 / 2.0,
+                                    title_top,
+                                    title_height,
+                                    item_size as f32,
+                                );
+                            }
+                        }
+                    }
                 }
-
-                let icon_y = y as i32;
-                let paint = PixmapPaint::default();
-                pixmap.draw_pixmap(
-                    icon_x,
-                    icon_y,
-                    window.icon.as_ref().as_ref(),
-                    &paint,
-                    transform,
-                    None,
-                );
             }
+            draw_text(
+                &mut pixmap,
+                transform,
+                &self.query,
+                &style,
+                self.width as f32 / 2.0,
+                config.panel_padding as f32,
+                config.text_row_height() as f32,
+                available as f32,
+            );
         }
 
-        swizzle_rgba_to_bgra(canvas.as_mut());
+        swizzle_rgba_to_bgra(canvas);
         self.draw_panel_shadow();
         if selected_on_child {
             self.draw_selected_indicator();
@@ -451,14 +688,18 @@ impl Switcher {
     }
 
     fn draw_selected_indicator(&mut self) {
-        let config = *app_config();
-        let Some(icon) = self
-            .windows
-            .get(self.selected)
-            .map(|window| window.icon.clone())
-        else {
+        let config = self.config.clone();
+        let Some(window) = self.windows.get(self.selected).cloned() else {
+            if let Some(indicator) = &self.selected_indicator {
+                indicator.surface.attach(None, 0, 0);
+                indicator.surface.commit();
+            }
             return;
         };
+        let thumbnail = self
+            .thumbnails
+            .as_ref()
+            .and_then(|thumbnails| thumbnails.get(window.id));
         let Some((item_x, item_y)) = self.selected_indicator_position() else {
             return;
         };
@@ -479,7 +720,7 @@ impl Switcher {
             .expect("create selected indicator buffer");
 
         {
-            let mut pixmap = PixmapMut::from_bytes(canvas.as_mut(), buffer_size, buffer_size)
+            let mut pixmap = PixmapMut::from_bytes(canvas, buffer_size, buffer_size)
                 .expect("selected indicator pixmap from buffer");
             pixmap.fill(Color::from_rgba8(0, 0, 0, 0));
 
@@ -493,13 +734,14 @@ impl Switcher {
                 config.corner_radius * 0.7,
             );
 
-            let mut paint = Paint::default();
-            paint.set_color(Color::from_rgba8(
-                config.selected_indicator_color.r,
-                config.selected_indicator_color.g,
-                config.selected_indicator_color.b,
-                selected_indicator_alpha(),
-            ));
+            let size = indicator.size as f32;
+            let mut paint = config.selected_indicator_color.paint(
+                opacity_alpha(config.selected_indicator_opacity),
+                0.0,
+                0.0,
+                size,
+                size,
+            );
             pixmap.fill_path(
                 &highlight,
                 &paint,
@@ -508,28 +750,29 @@ impl Switcher {
                 None,
             );
 
-            paint.set_color(Color::from_rgba8(
-                config.selected_indicator_border_color.r,
-                config.selected_indicator_border_color.g,
-                config.selected_indicator_border_color.b,
-                selected_indicator_border_alpha(),
-            ));
-            let mut stroke = Stroke::default();
-            stroke.width = config.indicator_border_width.max(1.0);
+            paint.set_color(
+                config
+                    .selected_indicator_border_color
+                    .with_alpha(opacity_alpha(config.selected_indicator_border_opacity)),
+            );
+            let stroke = Stroke {
+                width: config.indicator_border_width.max(1.0),
+                ..Stroke::default()
+            };
             pixmap.stroke_path(&highlight, &paint, &stroke, transform, None);
 
-            let pixmap_paint = PixmapPaint::default();
-            pixmap.draw_pixmap(
-                config.highlight_padding as i32,
-                config.highlight_padding as i32,
-                icon.as_ref().as_ref(),
-                &pixmap_paint,
+            draw_tile_content(
+                &mut pixmap,
                 transform,
-                None,
+                0.0,
+                0.0,
+                &window,
+                thumbnail.as_deref(),
+                &config,
             );
         }
 
-        swizzle_rgba_to_bgra(canvas.as_mut());
+        swizzle_rgba_to_bgra(canvas);
         indicator.subsurface.set_position(item_x, item_y);
         indicator.surface.set_buffer_scale(self.buffer_scale as i32);
         indicator
@@ -542,7 +785,7 @@ impl Switcher {
     }
 
     fn draw_panel_shadow(&mut self) {
-        let config = *app_config();
+        let config = self.config.clone();
         let Some(shadow) = self.panel_shadow.as_mut() else {
             return;
         };
@@ -564,11 +807,11 @@ impl Switcher {
             .expect("create panel shadow buffer");
 
         {
-            let mut pixmap = PixmapMut::from_bytes(canvas.as_mut(), buffer_width, buffer_height)
+            let mut pixmap = PixmapMut::from_bytes(canvas, buffer_width, buffer_height)
                 .expect("panel shadow pixmap from buffer");
             pixmap.fill(Color::from_rgba8(0, 0, 0, 0));
 
-            if spread > 0 && panel_border_alpha() > 0 {
+            if spread > 0 && opacity_alpha(config.panel_border_opacity) > 0 {
                 let transform =
                     Transform::from_scale(self.buffer_scale as f32, self.buffer_scale as f32);
                 draw_panel_shadow(
@@ -584,7 +827,7 @@ impl Switcher {
             }
         }
 
-        swizzle_rgba_to_bgra(canvas.as_mut());
+        swizzle_rgba_to_bgra(canvas);
         shadow
             .subsurface
             .set_position(-(spread as i32), -(spread as i32));
@@ -599,19 +842,7 @@ impl Switcher {
     }
 
     fn selected_indicator_position(&self) -> Option<(i32, i32)> {
-        if self.windows.is_empty() {
-            return None;
-        }
-        let config = *app_config();
-        let item_size = config.icon_size + config.highlight_padding * 2;
-        let total_width = self.windows.len() as i32 * item_size as i32
-            + (self.windows.len().saturating_sub(1) as i32 * config.icon_spacing as i32);
-        let available = self.width as i32 - (config.panel_padding as i32 * 2);
-        let start_x = (config.panel_padding as i32 + ((available - total_width) / 2)).max(0);
-        let y = self.height as i32 / 2 - (config.icon_size / 2) as i32;
-        let hover_y = y - config.highlight_padding as i32;
-        let item_x = start_x + self.selected as i32 * (item_size + config.icon_spacing) as i32;
-        Some((item_x, hover_y))
+        self.layout.item_position(self.selected)
     }
 
     fn cycle(&mut self, delta: i32, qh: &QueueHandle<Self>) {
@@ -620,13 +851,208 @@ impl Switcher {
         }
         let len = self.windows.len() as i32;
         let next = (self.selected as i32 + delta).rem_euclid(len) as usize;
-        if next != self.selected {
-            self.selected = next;
+        self.select(next, qh);
+    }
+
+    fn cycle_app(&mut self, delta: i32, qh: &QueueHandle<Self>) {
+        if self.scope == Scope::FocusedApp {
+            self.cycle(delta, qh);
+            return;
+        }
+        let all = self
+            .all_windows
+            .iter()
+            .flat_map(WindowEntry::ungroup)
+            .collect::<Vec<_>>();
+        let windows = same_app_as_focused(all);
+        // The focused window leads the MRU order, so index 0 is where the
+        // app's windows are cycled from.
+        self.scope = Scope::FocusedApp;
+        self.all_windows = windows.clone();
+        self.windows = windows;
+        self.query.clear();
+        self.hovered = None;
+        self.selected = delta.rem_euclid(self.windows.len().max(1) as i32) as usize;
+        self.redraw = true;
+        if !self.fit_to_windows() {
+            self.draw(qh);
+        }
+    }
+
+    /// Moves the selection along the grid. Down from the last row opens the
+    /// selected app's windows, as `expand` does.
+    fn step(&mut self, step: Step, qh: &QueueHandle<Self>) {
+        match self.layout.neighbor(self.selected, step) {
+            Some(idx) => self.select(idx, qh),
+            None if step == Step::Down => self.expand_selected(qh),
+            None => {}
+        }
+    }
+
+    fn select(&mut self, idx: usize, qh: &QueueHandle<Self>) {
+        if idx != self.selected && idx < self.windows.len() {
+            self.selected = idx;
             self.redraw = true;
             self.draw(qh);
         }
     }
 
+    /// Top of the icon strip, below the search query when there is one.
+    fn strip_top(&self) -> u32 {
+        let config = self.config.clone();
+        if self.query.is_empty() {
+            config.panel_padding
+        } else {
+            config.panel_padding + config.text_row_height()
+        }
+    }
+
+    /// Shows the windows matching `query`, with the best match selected.
+    fn set_query(&mut self, query: String, qh: &QueueHandle<Self>) {
+        let selected_id = self.windows.get(self.selected).map(|window| window.id);
+        self.query = query;
+        if self.query.is_empty() {
+            self.windows = self.all_windows.clone();
+            // A window picked out of a group by the query selects its tile.
+            self.selected = selected_id
+                .and_then(|id| {
+                    self.windows.iter().position(|window| {
+                        window.id == id || window.grouped.iter().any(|member| member.id == id)
+                    })
+                })
+                .unwrap_or(0);
+        } else {
+            self.windows = filter_windows(&self.query, &self.all_windows);
+            self.selected = 0;
+        }
+        self.hovered = None;
+        self.redraw = true;
+        if !self.fit_to_windows() {
+            self.draw(qh);
+        }
+    }
+
+    /// Resizes the panel to the shown windows. Returns whether the size
+    /// changed, in which case the next configure redraws.
+    fn fit_to_windows(&mut self) -> bool {
+        let config = self.config.clone();
+        self.layout = Layout::new(
+            config.layout,
+            self.windows.len(),
+            self.strip_top(),
+            self.output_logical_size,
+            &config,
+        );
+        let (width, height) = self.layout.size();
+        if (width, height) == (self.width, self.height) {
+            return false;
+        }
+        self.width = width;
+        self.height = height;
+        self.layer.set_size(width, height);
+        if let Some(effect) = &self.background_effect
+            && let Some(region) = blur_region(
+                &self.compositor,
+                width,
+                height,
+                config.corner_radius,
+                config.border_width,
+            )
+        {
+            effect.surface.set_blur_region(Some(region.wl_region()));
+        }
+        self.apply_layout();
+        self.layer.commit();
+        self.resize_pending = true;
+        true
+    }
+
+    fn key_action(&self, event: &KeyEvent) -> Option<KeyAction> {
+        // Evdev code 1 is Escape, for keymaps that do not produce a keysym
+        // for it.
+        let keysym = if event.raw_code == 1 {
+            Keysym::Escape
+        } else {
+            event.keysym
+        };
+        self.config.keys.action(keysym, &self.modifiers)
+    }
+
+    fn edit_query(&mut self, event: &KeyEvent, qh: &QueueHandle<Self>) {
+        match event.keysym {
+            Keysym::BackSpace => {
+                if !self.query.is_empty() {
+                    let mut query = self.query.clone();
+                    query.pop();
+                    self.set_query(query, qh);
+                }
+            }
+            _ => {
+                if let Some(text) = query_text(event) {
+                    let query = format!("{}{text}", self.query);
+                    self.set_query(query, qh);
+                }
+            }
+        }
+    }
+
+    /// Replaces the selected app's tile with the list of its windows.
+    fn expand_selected(&mut self, qh: &QueueHandle<Self>) {
+        let Some(tile) = self.windows.get(self.selected) else {
+            return;
+        };
+        if tile.grouped.is_empty() {
+            return;
+        }
+        let windows = tile.ungroup();
+        // The focused window leads its group; start on the one before it.
+        self.selected = usize::from(windows[0].is_focused);
+        self.all_windows = windows.clone();
+        self.windows = windows;
+        self.query.clear();
+        self.hovered = None;
+        self.redraw = true;
+        if !self.fit_to_windows() {
+            self.draw(qh);
+        }
+    }
+
+    /// Closes, kills or minimizes the selected window. Closed and killed
+    /// windows lose their tile; the switcher stays open while any remain.
+    fn act_on_selected(&mut self, action: KeyAction, qh: &QueueHandle<Self>) {
+        let Some(id) = self.windows.get(self.selected).map(|window| window.id) else {
+            return;
+        };
+        let result = {
+            let mut backend = self.backend.lock().unwrap();
+            match action {
+                KeyAction::Kill => backend.kill_window(id),
+                KeyAction::Minimize => backend.minimize_window(id),
+                _ => backend.close_window(id),
+            }
+        };
+        if let Err(err) = result {
+            eprintln!("witcher: {err:#}");
+            return;
+        }
+        if action == KeyAction::Minimize {
+            return;
+        }
+        remove_window(&mut self.all_windows, id);
+        remove_window(&mut self.windows, id);
+        if self.all_windows.is_empty() {
+            self.canceled = true;
+            self.exit = true;
+            return;
+        }
+        self.selected = self.selected.min(self.windows.len().saturating_sub(1));
+        self.hovered = None;
+        self.redraw = true;
+        if !self.fit_to_windows() {
+            self.draw(qh);
+        }
+    }
+
     fn finalize(&mut self) {
         if self.canceled {
             self.exit = true;
@@ -637,7 +1063,7 @@ impl Switcher {
         }
         self.finalized = true;
         if let Some(window) = self.windows.get(self.selected) {
-            let _ = focus_window(self.backend, window.id);
+            let _ = self.backend.lock().unwrap().focus_window(window.id);
         }
         self.exit = true;
     }
@@ -651,31 +1077,7 @@ impl Switcher {
     }
 
     fn hit_test(&self, position: (f64, f64)) -> Option<usize> {
-        if self.windows.is_empty() {
-            return None;
-        }
-        let config = *app_config();
-        let (x, y) = position;
-        let item_size = (config.icon_size + config.highlight_padding * 2) as f64;
-        let total_width = self.windows.len() as f64 * item_size
-            + (self.windows.len().saturating_sub(1) as f64 * config.icon_spacing as f64);
-        let available = self.width as f64 - (config.panel_padding as f64 * 2.0);
-        let start_x = (config.panel_padding as f64 + ((available - total_width) / 2.0)).max(0.0);
-        let y_top = (self.height as f64 / 2.0)
-            - (config.icon_size as f64 / 2.0)
-            - config.highlight_padding as f64;
-        if y < y_top || y > y_top + item_size {
-            return None;
-        }
-        let idx = ((x - start_x) / (item_size + config.icon_spacing as f64)).floor() as i32;
-        if idx < 0 || idx as usize >= self.windows.len() {
-            return None;
-        }
-        let item_x = start_x + idx as f64 * (item_size + config.icon_spacing as f64);
-        if x < item_x || x > item_x + item_size {
-            return None;
-        }
-        Some(idx as usize)
+        self.layout.hit_test(position)
     }
 }
 
@@ -748,15 +1150,26 @@ impl OutputHandler for Switcher {
         _qh: &QueueHandle<Self>,
         output: wl_output::WlOutput,
     ) {
-        if let Some(info) = self.output_state.info(&output) {
-            if let Some(size) = info.logical_size {
-                self.output_logical_size = Some(size);
-                let scale = info.scale_factor.max(1) as u32;
-                if scale != self.buffer_scale {
-                    self.buffer_scale = scale;
-                    self.layer.wl_surface().set_buffer_scale(scale as i32);
-                    self.redraw = true;
-                }
+        let Some(info) = self.output_state.info(&output) else {
+            return;
+        };
+        if self
+            .output_name
+            .as_ref()
+            .is_some_and(|name| info.name.as_ref() != Some(name))
+        {
+            return;
+        }
+        if let Some(size) = info.logical_size {
+            self.output_logical_size = Some(size);
+            let scale = info.scale_factor.max(1) as u32;
+            if scale != self.buffer_scale {
+                self.buffer_scale = scale;
+                self.layer.wl_surface().set_buffer_scale(scale as i32);
+                self.redraw = true;
+            }
+            // The output width decides where the tiles wrap.
+            if !self.fit_to_windows() {
                 self.apply_layout();
                 self.layer.commit();
             }
@@ -787,8 +1200,9 @@ impl LayerShellHandler for Switcher {
     ) {
         self.apply_layout();
 
-        if self.first_configure {
+        if self.first_configure || self.resize_pending {
             self.first_configure = false;
+            self.resize_pending = false;
             self.redraw = true;
             self.draw(qh);
         }
@@ -832,15 +1246,15 @@ impl SeatHandler for Switcher {
         _seat: wl_seat::WlSeat,
         capability: Capability,
     ) {
-        if capability == Capability::Keyboard {
-            if let Some(keyboard) = self.keyboard.take() {
-                keyboard.release();
-            }
+        if capability == Capability::Keyboard
+            && let Some(keyboard) = self.keyboard.take()
+        {
+            keyboard.release();
         }
-        if capability == Capability::Pointer {
-            if let Some(pointer) = self.pointer.take() {
-                pointer.release();
-            }
+        if capability == Capability::Pointer
+            && let Some(pointer) = self.pointer.take()
+        {
+            pointer.release();
         }
     }
 
@@ -879,22 +1293,26 @@ impl KeyboardHandler for Switcher {
         _serial: u32,
         event: KeyEvent,
     ) {
-        if is_escape_key(&event) {
-            self.canceled = true;
-            self.exit = true;
-            return;
-        }
-        match event.keysym {
-            Keysym::Tab => {
-                self.cycle(1, qh);
-            }
-            Keysym::ISO_Left_Tab => {
-                self.cycle(-1, qh);
+        let action = bound_action(self.key_action(&event), &self.query, &event);
+        match action {
+            Some(KeyAction::Cancel) => {
+                self.canceled = true;
+                self.exit = true;
             }
-            Keysym::Return | Keysym::KP_Enter => {
-                self.finalize();
+            Some(KeyAction::Next) => self.cycle(1, qh),
+            Some(KeyAction::Prev) => self.cycle(-1, qh),
+            Some(KeyAction::Accept) => self.finalize(),
+            Some(KeyAction::First) => self.select(0, qh),
+            Some(KeyAction::Last) => self.select(self.windows.len().saturating_sub(1), qh),
+            Some(action @ (KeyAction::Close | KeyAction::Kill | KeyAction::Minimize)) => {
+                self.act_on_selected(action, qh)
             }
-            _ => {}
+            Some(KeyAction::Expand) => self.expand_selected(qh),
+            Some(KeyAction::Left) => self.step(Step::Left, qh),
+            Some(KeyAction::Right) => self.step(Step::Right, qh),
+            Some(KeyAction::Up) => self.step(Step::Up, qh),
+            Some(KeyAction::Down) => self.step(Step::Down, qh),
+            None => self.edit_query(&event, qh),
         }
     }
 
@@ -906,7 +1324,7 @@ impl KeyboardHandler for Switcher {
         _serial: u32,
         event: KeyEvent,
     ) {
-        if is_escape_key(&event) {
+        if self.key_action(&event) == Some(KeyAction::Cancel) {
             self.canceled = true;
             self.exit = true;
             return;
@@ -914,7 +1332,7 @@ impl KeyboardHandler for Switcher {
         if self.canceled {
             return;
         }
-        if matches!(event.keysym, Keysym::Alt_L | Keysym::Alt_R) {
+        if self.config.keys.modifier.is_key(event.keysym) {
             self.finalize();
         }
     }
@@ -932,7 +1350,7 @@ impl KeyboardHandler for Switcher {
             return;
         }
         self.modifiers = modifiers;
-        if !modifiers.alt {
+        if !self.config.keys.modifier.is_held(&modifiers) {
             self.finalize();
         }
     }
@@ -960,23 +1378,19 @@ impl PointerHandler for Switcher {
                         needs_redraw = true;
                     }
                 }
-                PointerEventKind::Leave { .. } => {
-                    if self.hovered.is_some() {
-                        self.hovered = None;
-                        self.redraw = true;
-                        needs_redraw = true;
-                    }
+                PointerEventKind::Leave { .. } if self.hovered.is_some() => {
+                    self.hovered = None;
+                    self.redraw = true;
+                    needs_redraw = true;
                 }
-                PointerEventKind::Press { button, .. } => {
-                    if button == BTN_LEFT {
-                        if let Some(idx) = self.hit_test(event.position) {
-                            if idx != self.selected {
-                                self.selected = idx;
-                                self.redraw = true;
-                                needs_redraw = true;
-                            }
-                            self.finalize();
+                PointerEventKind::Press { button, .. } if button == BTN_LEFT => {
+                    if let Some(idx) = self.hit_test(event.position) {
+                        if idx != self.selected {
+                            self.selected = idx;
+                            self.redraw = true;
+                            needs_redraw = true;
                         }
+                        self.finalize();
                     }
                 }
                 _ => {}
@@ -988,6 +1402,18 @@ impl PointerHandler for Switcher {
     }
 }
 
+impl ThumbnailHandler for Switcher {
+    fn thumbnails(&mut self) -> &mut Thumbnails {
+        self.thumbnails
+            .as_mut()
+            .expect("thumbnail events without thumbnails")
+    }
+
+    fn thumbnail_updated(&mut self, _qh: &QueueHandle<Self>, _id: u64) {
+        self.thumbnails_changed = true;
+    }
+}
+
 impl ShmHandler for Switcher {
     fn shm_state(&mut self) -> &mut Shm {
         &mut self.shm
@@ -1003,6 +1429,7 @@ delegate_pointer!(Switcher);
 delegate_layer!(Switcher);
 delegate_registry!(Switcher);
 delegate_subcompositor!(Switcher);
+delegate_thumbnails!(Switcher);
 delegate_noop!(Switcher: ignore ExtBackgroundEffectManagerV1);
 delegate_noop!(Switcher: ExtBackgroundEffectSurfaceV1);
 
@@ -1014,21 +1441,225 @@ impl ProvidesRegistryState for Switcher {
     registry_handlers![OutputState, SeatState];
 }
 
-fn layout_size(count: usize, icon_size: u32) -> (u32, u32) {
-    if count == 0 {
-        return (0, 0);
-    }
-    let config = *app_config();
-    let item_size = icon_size + config.highlight_padding * 2;
-    let width = config.panel_padding * 2
-        + count as u32 * item_size
-        + (count as u32 - 1) * config.icon_spacing;
-    let height = config.panel_padding * 2 + item_size;
-    (width, height)
+/// The windows sharing the focused window's app id, or all of them when
+/// nothing is focused.
+fn same_app_as_focused(windows: Vec<WindowEntry>) -> Vec<WindowEntry> {
+    let Some(app_id) = windows
+        .iter()
+        .find(|window| window.is_focused)
+        .map(|window| window.app_id.clone())
+    else {
+        return windows;
+    };
+    windows
+        .into_iter()
+        .filter(|window| window.app_id == app_id)
+        .collect()
+}
+
+/// Drops window `id`, letting the next window of its app stand in for a
+/// group the window led.
+fn remove_window(windows: &mut Vec<WindowEntry>, id: u64) {
+    windows.retain_mut(|window| {
+        window.grouped.retain(|other| other.id != id);
+        if window.id != id {
+            return true;
+        }
+        let mut rest = std::mem::take(&mut window.grouped).into_iter();
+        let Some(mut next) = rest.next() else {
+            return false;
+        };
+        next.grouped = rest.collect();
+        *window = next;
+        true
+    });
+}
+
+/// The action a key press runs, or `None` when it goes to the query. Once
+/// a query is being typed, printable keys extend it even when they are
+/// bound, so a letter binding cannot cut a search short.
+fn bound_action(action: Option<KeyAction>, query: &str, event: &KeyEvent) -> Option<KeyAction> {
+    action.filter(|_| query.is_empty() || query_text(event).is_none())
+}
+
+/// Text a key press types into the search query, if any.
+fn query_text(event: &KeyEvent) -> Option<&str> {
+    event
+        .utf8
+        .as_deref()
+        .filter(|text| !text.is_empty() && !text.chars().any(char::is_control))
 }
 
-fn load_windows(backend: BackendKind, icon_cache: &mut IconCache) -> Result<Vec<WindowEntry>> {
-    let windows = backend_windows(backend)?;
+/// Draws one row of the list layout: the selection bar or hover outline,
+/// then the icon, title and app name.
+#[allow(clippy::too_many_arguments)]
+fn draw_list_row(
+    pixmap: &mut PixmapMut<'_>,
+    transform: Transform,
+    list: &ListLayout,
+    (x, y): (f32, f32),
+    window: &WindowEntry,
+    is_selected: bool,
+    is_hovered: bool,
+    config: &crate::config::AppConfig,
+) {
+    let row_width = list.row_width() as f32;
+    let row_height = list.row_height as f32;
+    if is_selected || is_hovered {
+        let bar = rounded_rect_path(x, y, row_width, row_height, config.corner_radius * 0.4);
+        if is_selected {
+            let paint = config.selected_indicator_color.paint(
+                opacity_alpha(config.selected_indicator_opacity),
+                x,
+                y,
+                row_width,
+                row_height,
+            );
+            pixmap.fill_path(&bar, &paint, tiny_skia::FillRule::Winding, transform, None);
+        }
+        let (color, alpha) = if is_selected {
+            (
+                config.selected_indicator_border_color,
+                opacity_alpha(config.selected_indicator_border_opacity),
+            )
+        } else {
+            (
+                config.hover_border_color,
+                opacity_alpha(config.panel_opacity),
+            )
+        };
+        let mut paint = Paint::default();
+        paint.set_color(color.with_alpha(alpha));
+        let stroke = Stroke {
+            width: config.indicator_border_width.max(1.0),
+            ..Stroke::default()
+        };
+        pixmap.stroke_path(&bar, &paint, &stroke, transform, None);
+    }
+
+    let padding = list.row_padding as f32;
+    let icon_size = list.icon_size as f32;
+    let factor = icon_size / window.icon.width().max(1) as f32;
+    let icon_transform = transform
+        .pre_translate(x + padding, y + padding)
+        .pre_scale(factor, factor);
+    let paint = PixmapPaint {
+        quality: FilterQuality::Bilinear,
+        ..PixmapPaint::default()
+    };
+    pixmap.draw_pixmap(
+        0,
+        0,
+        window.icon.as_ref().as_ref(),
+        &paint,
+        icon_transform,
+        None,
+    );
+
+    let text_left = x + padding * 2.0 + icon_size;
+    let text_width = row_width - (text_left - x) - padding;
+    let line_height = icon_size / 2.0;
+    let title_style = TextStyle {
+        family: &config.font_family,
+        size: config.font_size,
+        color: config.font_color,
+        alpha: 255,
+    };
+    let title = window.title.as_deref().unwrap_or(&window.app_id);
+    draw_text_left(
+        pixmap,
+        transform,
+        title,
+        &title_style,
+        text_left,
+        y + padding,
+        line_height,
+        text_width,
+    );
+    let app_style = TextStyle {
+        size: config.font_size * 0.85,
+        alpha: 170,
+        ..title_style
+    };
+    let app_name = window.app_name.as_deref().unwrap_or(&window.app_id);
+    let app_line = match window.window_count() {
+        1 => app_name.to_string(),
+        count => format!("{app_name} · {count} windows"),
+    };
+    draw_text_left(
+        pixmap,
+        transform,
+        &app_line,
+        &app_style,
+        text_left,
+        y + padding + line_height,
+        line_height,
+        text_width,
+    );
+}
+
+/// Binds the outputs on a queue of their own to look one up by its name,
+/// before the panel exists to receive output events.
+struct OutputProbe {
+    output_state: OutputState,
+}
+
+impl OutputHandler for OutputProbe {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+delegate_output!(OutputProbe);
+
+fn find_output(conn: &Connection, globals: &GlobalList, name: &str) -> Option<wl_output::WlOutput> {
+    let mut queue = conn.new_event_queue::<OutputProbe>();
+    let mut probe = OutputProbe {
+        output_state: OutputState::new(globals, &queue.handle()),
+    };
+    queue.roundtrip(&mut probe).ok()?;
+    let output_state = &probe.output_state;
+    output_state.outputs().find(|output| {
+        output_state
+            .info(output)
+            .is_some_and(|info| info.name.as_deref() == Some(name))
+    })
+}
+
+/// The backend's windows with the window rules applied: hidden windows are
+/// left out, and pins, icons and labels are set for the MRU ordering and
+/// drawing that follow.
+fn load_windows(
+    backend: &mut dyn Backend,
+    icon_cache: &mut IconCache,
+    filter: WindowFilter,
+    rules: &[Rule],
+) -> Result<Vec<WindowEntry>> {
+    let mut windows = backend.windows()?;
     let mut app_ids_by_pid = HashMap::new();
     for window in &windows {
         let Some(pid) = window.pid else {
@@ -1039,30 +1670,156 @@ fn load_windows(backend: BackendKind, icon_cache: &mut IconCache) -> Result<Vec<
         };
         app_ids_by_pid.entry(pid).or_insert_with(|| app_id.clone());
     }
+    if filter != WindowFilter::All {
+        let focus = backend
+            .focused_place()
+            .context("find the focused workspace")?;
+        windows = filter_by_place(windows, filter, &focus);
+    }
 
     let mut seen = HashSet::new();
     let mut entries = Vec::new();
     for window in windows {
-        let app_id = window.app_id.or_else(|| {
-            window
-                .pid
-                .and_then(|pid| app_ids_by_pid.get(&pid).cloned())
-        })
-        .or(window.process_name)
-        .unwrap_or_else(|| "application-x-executable".to_string());
+        let app_id = window
+            .app_id
+            .clone()
+            .or_else(|| window.pid.and_then(|pid| app_ids_by_pid.get(&pid).cloned()))
+            .or_else(|| window.process_name.clone())
+            .unwrap_or_else(|| "application-x-executable".to_string());
         if !seen.insert(window.id) {
             continue;
         }
-        let icon = icon_cache.icon_for(&app_id, window.title.as_deref());
+        let actions = actions_for(rules, &window, &app_id);
+        if actions.hide {
+            continue;
+        }
+        let icon = actions
+            .icon
+            .and_then(|name| icon_cache.named_icon(&name))
+            .unwrap_or_else(|| icon_cache.icon_for(&app_id, window.title.as_deref()));
         entries.push(WindowEntry {
             id: window.id,
             is_focused: window.is_focused,
+            app_name: icon_cache.app_name(&app_id),
+            app_id,
+            title: actions.label.or(window.title),
             icon,
+            pinned: actions.pin_first,
+            grouped: Vec::new(),
         });
     }
     Ok(entries)
 }
 
+/// Draws what goes inside the tile whose top-left corner is at `x`, `y`:
+/// the window's thumbnail with its icon in the corner, or just the icon.
+fn draw_tile_content(
+    pixmap: &mut PixmapMut<'_>,
+    transform: Transform,
+    x: f32,
+    y: f32,
+    window: &WindowEntry,
+    thumbnail: Option<&Pixmap>,
+    config: &crate::config::AppConfig,
+) {
+    let Some(thumbnail) = thumbnail else {
+        pixmap.draw_pixmap(
+            (x + config.highlight_padding as f32) as i32,
+            (y + config.highlight_padding as f32) as i32,
+            window.icon.as_ref().as_ref(),
+            &PixmapPaint::default(),
+            transform,
+            None,
+        );
+        draw_count_badge(pixmap, transform, x, y, window.window_count(), config);
+        return;
+    };
+
+    // Thumbnails are captured at device pixels, so they are placed without
+    // the scale transform.
+    let inset = config.highlight_padding as f32 / 2.0;
+    let size = (config.icon_size + config.highlight_padding) as f32;
+    let scale = transform.sx;
+    let width = thumbnail.width() as f32 / scale;
+    let height = thumbnail.height() as f32 / scale;
+    let left = x + inset + (size - width) / 2.0;
+    let top = y + inset + (size - height) / 2.0;
+    pixmap.draw_pixmap(
+        (left * scale + transform.tx).round() as i32,
+        (top * scale + transform.ty).round() as i32,
+        thumbnail.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+
+    let badge = size * 0.45;
+    let factor = badge / window.icon.width().max(1) as f32;
+    let icon_transform = transform
+        .pre_translate(left + width - badge * 0.8, top + height - badge * 0.8)
+        .pre_scale(factor, factor);
+    let paint = PixmapPaint {
+        quality: FilterQuality::Bilinear,
+        ..PixmapPaint::default()
+    };
+    pixmap.draw_pixmap(
+        0,
+        0,
+        window.icon.as_ref().as_ref(),
+        &paint,
+        icon_transform,
+        None,
+    );
+    draw_count_badge(pixmap, transform, x, y, window.window_count(), config);
+}
+
+/// Draws the number of windows an app tile stands for on the icon's top
+/// right corner. Single windows get no badge.
+fn draw_count_badge(
+    pixmap: &mut PixmapMut<'_>,
+    transform: Transform,
+    x: f32,
+    y: f32,
+    count: usize,
+    config: &crate::config::AppConfig,
+) {
+    if count < 2 {
+        return;
+    }
+    let radius = config.text_row_height() as f32 / 2.0;
+    let center_x = x + (config.highlight_padding + config.icon_size) as f32;
+    let center_y = y + config.highlight_padding as f32;
+    let Some(circle) = PathBuilder::from_circle(center_x, center_y, radius) else {
+        return;
+    };
+    let mut paint = Paint::default();
+    paint.set_color(config.font_color.with_alpha(230));
+    paint.anti_alias = true;
+    pixmap.fill_path(
+        &circle,
+        &paint,
+        tiny_skia::FillRule::Winding,
+        transform,
+        None,
+    );
+    let style = TextStyle {
+        family: &config.font_family,
+        size: config.font_size * 0.85,
+        color: config.panel_background_color.base(),
+        alpha: 255,
+    };
+    draw_text(
+        pixmap,
+        transform,
+        &count.to_string(),
+        &style,
+        center_x,
+        center_y - radius,
+        radius * 2.0,
+        radius * 2.0,
+    );
+}
+
 fn draw_selected_indicator_shadow(
     pixmap: &mut PixmapMut<'_>,
     transform: Transform,
@@ -1073,7 +1830,7 @@ fn draw_selected_indicator_shadow(
     config: &crate::config::AppConfig,
 ) {
     let spread = config.selected_indicator_shadow_size.max(0.0);
-    let base_alpha = selected_indicator_border_alpha();
+    let base_alpha = opacity_alpha(config.selected_indicator_border_opacity);
     if spread <= 0.0 || base_alpha == 0 {
         return;
     }
@@ -1093,12 +1850,11 @@ fn draw_selected_indicator_shadow(
             radius + expand,
         );
         let mut paint = Paint::default();
-        paint.set_color(Color::from_rgba8(
-            config.selected_indicator_border_color.r,
-            config.selected_indicator_border_color.g,
-            config.selected_indicator_border_color.b,
-            layer_alpha,
-        ));
+        paint.set_color(
+            config
+                .selected_indicator_border_color
+                .with_alpha(layer_alpha),
+        );
         pixmap.fill_path(
             &shadow,
             &paint,
@@ -1109,6 +1865,7 @@ fn draw_selected_indicator_shadow(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_panel_shadow(
     pixmap: &mut PixmapMut<'_>,
     transform: Transform,
@@ -1120,7 +1877,7 @@ fn draw_panel_shadow(
     config: &crate::config::AppConfig,
 ) {
     let spread = config.panel_shadow_size.max(0.0);
-    let base_alpha = panel_border_alpha();
+    let base_alpha = opacity_alpha(config.panel_border_opacity);
     if spread <= 0.0 || base_alpha == 0 {
         return;
     }
@@ -1140,12 +1897,7 @@ fn draw_panel_shadow(
             radius + expand,
         );
         let mut paint = Paint::default();
-        paint.set_color(Color::from_rgba8(
-            config.panel_shadow_color.r,
-            config.panel_shadow_color.g,
-            config.panel_shadow_color.b,
-            layer_alpha,
-        ));
+        paint.set_color(config.panel_shadow_color.with_alpha(layer_alpha));
         pixmap.fill_path(
             &shadow,
             &paint,
@@ -1156,6 +1908,7 @@ fn draw_panel_shadow(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_background_effect(
     globals: &GlobalList,
     qh: &QueueHandle<Switcher>,
@@ -1170,6 +1923,22 @@ fn create_background_effect(
         .bind::<ExtBackgroundEffectManagerV1, _, _>(qh, 1..=1, ())
         .ok()?;
     let effect = manager.get_background_effect(surface, qh, ());
+    let region = blur_region(compositor, width, height, radius, border_width)?;
+    effect.set_blur_region(Some(region.wl_region()));
+
+    Some(BackgroundEffect {
+        _manager: manager,
+        surface: effect,
+    })
+}
+
+fn blur_region(
+    compositor: &CompositorState,
+    width: u32,
+    height: u32,
+    radius: f32,
+    border_width: f32,
+) -> Option<Region> {
     let region = Region::new(compositor).ok()?;
     let inset = (border_width.max(0.0).ceil() as i32 - 1).max(0);
     add_rounded_rect_region(
@@ -1180,12 +1949,7 @@ fn create_background_effect(
         height as i32 - inset * 2,
         (radius - inset as f32).max(0.0),
     );
-    effect.set_blur_region(Some(region.wl_region()));
-
-    Some(BackgroundEffect {
-        _manager: manager,
-        _surface: effect,
-    })
+    Some(region)
 }
 
 fn create_panel_shadow(
@@ -1194,8 +1958,8 @@ fn create_panel_shadow(
     subcompositor: &SubcompositorState,
     parent: &wl_surface::WlSurface,
     buffer_scale: u32,
+    config: &AppConfig,
 ) -> PanelShadow {
-    let config = *app_config();
     let spread = config.panel_shadow_size.max(0.0).ceil() as u32;
     let (subsurface, surface) = subcompositor.create_subsurface(parent.clone(), qh);
     subsurface.set_sync();
@@ -1215,6 +1979,7 @@ fn create_panel_shadow(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_selected_indicator(
     globals: &GlobalList,
     qh: &QueueHandle<Switcher>,
@@ -1223,8 +1988,8 @@ fn create_selected_indicator(
     parent: &wl_surface::WlSurface,
     size: u32,
     buffer_scale: u32,
+    config: &AppConfig,
 ) -> SelectedIndicator {
-    let config = *app_config();
     let (subsurface, surface) = subcompositor.create_subsurface(parent.clone(), qh);
     subsurface.set_sync();
     subsurface.place_above(parent);
@@ -1318,7 +2083,14 @@ fn drain_wake_pipe(wake: &mut UnixStream) {
     }
 }
 
-fn poll_wayland_and_wake(wayland_fd: i32, wake_fd: i32) -> Result<(bool, bool)> {
+fn poll_wayland_and_wake(
+    wayland_fd: i32,
+    wake_fd: i32,
+    timeout: Option<Duration>,
+) -> Result<(bool, bool)> {
+    let timeout = timeout
+        .map(|timeout| timeout.as_millis().min(i32::MAX as u128) as i32)
+        .unwrap_or(-1);
     let mut fds = [
         libc::pollfd {
             fd: wayland_fd,
@@ -1333,7 +2105,7 @@ fn poll_wayland_and_wake(wayland_fd: i32, wake_fd: i32) -> Result<(bool, bool)>
     ];
 
     loop {
-        let rc = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+        let rc = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
         if rc < 0 {
             let err = std::io::Error::last_os_error();
             if err.kind() == std::io::ErrorKind::Interrupted {
@@ -1363,6 +2135,38 @@ fn rounded_rect_path(x: f32, y: f32, width: f32, height: f32, radius: f32) -> ti
     pb.finish().expect("rounded rect path")
 }
 
-fn is_escape_key(event: &KeyEvent) -> bool {
-    matches!(event.keysym, Keysym::Escape | Keysym::Cancel) || matches!(event.raw_code, 1 | 9)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::KeyBindings;
+
+    fn press(keysym: Keysym, text: &str) -> KeyEvent {
+        KeyEvent {
+            time: 0,
+            raw_code: 0,
+            keysym,
+            utf8: Some(text.to_string()),
+        }
+    }
+
+    #[test]
+    fn q_closes_until_a_query_is_typed() {
+        let keys = KeyBindings::default();
+        let alt = Modifiers {
+            alt: true,
+            ..Modifiers::default()
+        };
+        let q = press(Keysym::q, "q");
+        assert_eq!(
+            bound_action(keys.action(q.keysym, &alt), "", &q),
+            Some(KeyAction::Close)
+        );
+        assert_eq!(bound_action(keys.action(q.keysym, &alt), "b", &q), None);
+
+        let delete = press(Keysym::Delete, "\u{7f}");
+        assert_eq!(
+            bound_action(keys.action(delete.keysym, &alt), "b", &delete),
+            Some(KeyAction::Close)
+        );
+    }
 }
@@ -0,0 +1,264 @@
+use rustybuzz::ttf_parser::{GlyphId, OutlineBuilder};
+use rustybuzz::{Face, UnicodeBuffer};
+use std::sync::OnceLock;
+use tiny_skia::{FillRule, Paint, PathBuilder, PixmapMut, Transform};
+
+use crate::color::Rgba;
+
+const ELLIPSIS: &str = "…";
+
+const SANS_SERIF: &[&str] = &[
+    "Cantarell",
+    "Noto Sans",
+    "DejaVu Sans",
+    "Liberation Sans",
+    "Inter",
+];
+const SERIF: &[&str] = &["Noto Serif", "DejaVu Serif", "Liberation Serif"];
+const MONOSPACE: &[&str] = &[
+    "Noto Sans Mono",
+    "DejaVu Sans Mono",
+    "Liberation Mono",
+];
+
+/// System fonts, found through the fontconfig configuration. Scanning the
+/// font directories is slow, so it happens once per daemon.
+static FONTS: OnceLock<fontdb::Database> = OnceLock::new();
+
+fn database() -> &'static fontdb::Database {
+    FONTS.get_or_init(|| {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        db
+    })
+}
+
+/// Resolves a family name to a face. Generic names map to common families
+/// since fontdb does not read fontconfig aliases.
+fn find_face(db: &fontdb::Database, family: &str) -> Option<fontdb::ID> {
+    let candidates = match family.to_ascii_lowercase().as_str() {
+        "sans-serif" | "sans" => SANS_SERIF.to_vec(),
+        "serif" => SERIF.to_vec(),
+        "monospace" | "mono" => MONOSPACE.to_vec(),
+        _ => vec![family],
+    };
+    candidates
+        .into_iter()
+        .chain(SANS_SERIF.iter().copied())
+        .find_map(|name| {
+            db.query(&fontdb::Query {
+                families: &[fontdb::Family::Name(name)],
+                ..fontdb::Query::default()
+            })
+        })
+        .or_else(|| db.faces().next().map(|face| face.id))
+}
+
+fn with_face<T>(family: &str, f: impl FnOnce(&Face<'_>) -> T) -> Option<T> {
+    let db = database();
+    let id = find_face(db, family)?;
+    db.with_face_data(id, |data, index| Face::from_slice(data, index).map(|face| f(&face)))
+        .flatten()
+}
+
+pub struct TextStyle<'a> {
+    pub family: &'a str,
+    pub size: f32,
+    pub color: Rgba,
+    pub alpha: u8,
+}
+
+/// Draws `text` on one line, horizontally centered on `center_x` and
+/// vertically centered in the row starting at `top`. Text wider than
+/// `max_width` is cut and ends in an ellipsis.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_text(
+    pixmap: &mut PixmapMut<'_>,
+    transform: Transform,
+    text: &str,
+    style: &TextStyle<'_>,
+    center_x: f32,
+    top: f32,
+    row_height: f32,
+    max_width: f32,
+) {
+    draw_line(
+        pixmap, transform, text, style, center_x, true, top, row_height, max_width,
+    );
+}
+
+/// Like [`draw_text`], but starting at `left` instead of centered.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_text_left(
+    pixmap: &mut PixmapMut<'_>,
+    transform: Transform,
+    text: &str,
+    style: &TextStyle<'_>,
+    left: f32,
+    top: f32,
+    row_height: f32,
+    max_width: f32,
+) {
+    draw_line(
+        pixmap, transform, text, style, left, false, top, row_height, max_width,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_line(
+    pixmap: &mut PixmapMut<'_>,
+    transform: Transform,
+    text: &str,
+    style: &TextStyle<'_>,
+    x: f32,
+    centered: bool,
+    top: f32,
+    row_height: f32,
+    max_width: f32,
+) {
+    if text.is_empty() || max_width <= 0.0 {
+        return;
+    }
+    with_face(style.family, |face| {
+        let scale = style.size / face.units_per_em() as f32;
+        let text = ellipsize(face, text, max_width / scale);
+        let glyphs = shape(face, &text);
+        let width = glyphs
+            .glyph_positions()
+            .iter()
+            .map(|pos| pos.x_advance)
+            .sum::<i32>() as f32
+            * scale;
+        let ascender = face.ascender() as f32 * scale;
+        let descender = face.descender() as f32 * scale;
+        let baseline = top + (row_height - (ascender - descender)) / 2.0 + ascender;
+
+        let mut outline = GlyphOutline {
+            builder: PathBuilder::new(),
+            x: 0.0,
+            y: 0.0,
+            scale,
+        };
+        let left = if centered { x - width / 2.0 } else { x };
+        let mut pen_x = 0i32;
+        for (info, pos) in glyphs.glyph_infos().iter().zip(glyphs.glyph_positions()) {
+            outline.x = left + (pen_x + pos.x_offset) as f32 * scale;
+            outline.y = baseline - pos.y_offset as f32 * scale;
+            face.outline_glyph(GlyphId(info.glyph_id as u16), &mut outline);
+            pen_x += pos.x_advance;
+        }
+        let Some(path) = outline.builder.finish() else {
+            return;
+        };
+        let mut paint = Paint::default();
+        paint.set_color(style.color.with_alpha(style.alpha));
+        pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+    });
+}
+
+fn shape(face: &Face<'_>, text: &str) -> rustybuzz::GlyphBuffer {
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+    rustybuzz::shape(face, &[], buffer)
+}
+
+fn advance(face: &Face<'_>, text: &str) -> i32 {
+    shape(face, text)
+        .glyph_positions()
+        .iter()
+        .map(|pos| pos.x_advance)
+        .sum()
+}
+
+/// Longest prefix of `text` that fits `max_width` font units together with
+/// an ellipsis, or `text` itself when it already fits.
+fn ellipsize(face: &Face<'_>, text: &str, max_width: f32) -> String {
+    if advance(face, text) as f32 <= max_width {
+        return text.to_string();
+    }
+    let boundaries = text
+        .char_indices()
+        .map(|(idx, _)| idx)
+        .skip(1)
+        .collect::<Vec<_>>();
+    let fits = |end: usize| {
+        let candidate = format!("{}{ELLIPSIS}", text[..end].trim_end());
+        advance(face, &candidate) as f32 <= max_width
+    };
+    let count = boundaries.partition_point(|&end| fits(end));
+    match count {
+        0 => ELLIPSIS.to_string(),
+        count => format!("{}{ELLIPSIS}", text[..boundaries[count - 1]].trim_end()),
+    }
+}
+
+struct GlyphOutline {
+    builder: PathBuilder,
+    x: f32,
+    y: f32,
+    scale: f32,
+}
+
+impl GlyphOutline {
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.x + x * self.scale, self.y - y * self.scale)
+    }
+}
+
+impl OutlineBuilder for GlyphOutline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        self.builder.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        self.builder.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (x1, y1) = self.point(x1, y1);
+        let (x, y) = self.point(x, y);
+        self.builder.quad_to(x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (x1, y1) = self.point(x1, y1);
+        let (x2, y2) = self.point(x2, y2);
+        let (x, y) = self.point(x, y);
+        self.builder.cubic_to(x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        self.builder.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_text_is_cut_with_ellipsis() {
+        let result = with_face("sans-serif", |face| {
+            let short = advance(face, "Terminal") as f32;
+            let long = "Terminal — a very long window title that does not fit";
+            (
+                ellipsize(face, "Terminal", short),
+                ellipsize(face, long, short * 1.5),
+                advance(face, &ellipsize(face, long, short * 1.5)) as f32 <= short * 1.5,
+                ellipsize(face, long, 1.0),
+            )
+        });
+        let Some((fits, cut, cut_fits, tiny)) = result else {
+            eprintln!("no system fonts available, skipping");
+            return;
+        };
+        assert_eq!(fits, "Terminal");
+        assert!(cut.starts_with("Terminal"));
+        assert!(cut.ends_with(ELLIPSIS));
+        assert!(cut_fits);
+        assert_eq!(tiny, ELLIPSIS);
+    }
+}
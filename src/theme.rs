@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::thread;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedValue, Value};
+
+use crate::config::{self, ColorScheme};
+
+const PORTAL_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_SETTINGS: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+/// Themes that ship with witcher. A file of the same name in the themes
+/// directory takes precedence.
+const BUILTIN_THEMES: [(&str, &str); 4] = [
+    ("dark", include_str!("themes/dark.toml")),
+    ("light", include_str!("themes/light.toml")),
+    ("nord", include_str!("themes/nord.toml")),
+    ("gruvbox", include_str!("themes/gruvbox.toml")),
+];
+
+/// The text of theme `name`, with where it came from for error messages.
+pub fn find(name: &str) -> Result<(String, String), String> {
+    if let Some(path) = user_theme_path(name)
+        && path.exists()
+    {
+        let text = std::fs::read_to_string(&path)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        return Ok((text, path.display().to_string()));
+    }
+    BUILTIN_THEMES
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, text)| (text.to_string(), format!("built-in theme `{name}`")))
+        .ok_or_else(|| {
+            let names = BUILTIN_THEMES.map(|(name, _)| name).join(", ");
+            format!("unknown theme `{name}`, the built-in themes are {names}")
+        })
+}
+
+/// `themes/<name>.toml` next to the config file.
+fn user_theme_path(name: &str) -> Option<PathBuf> {
+    // Names are file names, not paths.
+    if name.contains('/') {
+        return None;
+    }
+    config::config_dir().map(|dir| dir.join("themes").join(format!("{name}.toml")))
+}
+
+/// The desktop's light or dark preference from the settings portal, or
+/// `None` when it has none or there is no portal.
+pub fn system_color_scheme() -> Option<ColorScheme> {
+    let conn = Connection::session().ok()?;
+    let proxy = portal_settings(&conn).ok()?;
+    let value = proxy
+        .call::<_, _, OwnedValue>("ReadOne", &(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY))
+        // Portals before version 2 only have the deprecated `Read`.
+        .or_else(|_| proxy.call("Read", &(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY)))
+        .ok()?;
+    color_scheme_from(&value)
+}
+
+/// Reloads the config whenever the desktop switches between light and dark.
+pub fn watch_color_scheme() -> Result<()> {
+    let conn = Connection::session().context("connect to session bus")?;
+    thread::spawn(move || {
+        let changes = portal_settings(&conn).and_then(|proxy| {
+            proxy.receive_signal_with_args(
+                "SettingChanged",
+                &[(0, APPEARANCE_NAMESPACE), (1, COLOR_SCHEME_KEY)],
+            )
+        });
+        let changes = match changes {
+            Ok(changes) => changes,
+            Err(err) => {
+                eprintln!("witcher: not following the color scheme: {err}");
+                return;
+            }
+        };
+        for _ in changes {
+            if let Err(err) = config::reload() {
+                eprintln!("witcher: failed to reload config: {err:#}");
+            }
+        }
+    });
+    Ok(())
+}
+
+fn portal_settings(conn: &Connection) -> zbus::Result<Proxy<'static>> {
+    Proxy::new(conn, PORTAL_BUS_NAME, PORTAL_OBJECT_PATH, PORTAL_SETTINGS)
+}
+
+/// Maps the portal value, 1 for dark and 2 for light, which `Read` wraps in
+/// an extra variant.
+fn color_scheme_from(value: &Value) -> Option<ColorScheme> {
+    match value {
+        Value::Value(inner) => color_scheme_from(inner),
+        Value::U32(1) => Some(ColorScheme::Dark),
+        Value::U32(2) => Some(ColorScheme::Light),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portal_values_map_to_schemes() {
+        assert_eq!(color_scheme_from(&Value::U32(1)), Some(ColorScheme::Dark));
+        assert_eq!(
+            color_scheme_from(&Value::Value(Box::new(Value::U32(2)))),
+            Some(ColorScheme::Light)
+        );
+        assert_eq!(color_scheme_from(&Value::U32(0)), None);
+    }
+}
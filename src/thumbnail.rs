@@ -0,0 +1,763 @@
+use smithay_client_toolkit::shm::{
+    Shm,
+    slot::{Buffer, SlotPool},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tiny_skia::Pixmap;
+use wayland_client::{
+    Connection, Dispatch, QueueHandle, WEnum, event_created_child, globals::GlobalList,
+    protocol::wl_shm,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use wayland_protocols::ext::image_capture_source::v1::client::{
+    ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+    ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+};
+use wayland_protocols::ext::image_copy_capture::v1::client::{
+    ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1},
+    ext_image_copy_capture_manager_v1::{self, ExtImageCopyCaptureManagerV1},
+    ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+};
+
+use self::hyprland_export::{
+    hyprland_toplevel_export_frame_v1::{self, HyprlandToplevelExportFrameV1},
+    hyprland_toplevel_export_manager_v1::HyprlandToplevelExportManagerV1,
+};
+
+#[allow(dead_code, non_upper_case_globals, clippy::all)]
+pub mod hyprland_export {
+    use wayland_client;
+    use wayland_client::protocol::*;
+
+    pub mod __interfaces {
+        use wayland_client::backend as wayland_backend;
+        use wayland_client::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/hyprland-toplevel-export-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_client_code!("protocols/hyprland-toplevel-export-v1.xml");
+}
+
+/// How often a window is captured again while the switcher is open.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Formats whose pixels we know how to read, most preferred first.
+const FORMATS: [wl_shm::Format; 4] = [
+    wl_shm::Format::Argb8888,
+    wl_shm::Format::Xrgb8888,
+    wl_shm::Format::Abgr8888,
+    wl_shm::Format::Xbgr8888,
+];
+
+pub trait ThumbnailHandler:
+    Dispatch<HyprlandToplevelExportManagerV1, ()>
+    + Dispatch<HyprlandToplevelExportFrameV1, u64>
+    + Dispatch<ExtForeignToplevelListV1, ()>
+    + Dispatch<ExtForeignToplevelHandleV1, ()>
+    + Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()>
+    + Dispatch<ExtImageCaptureSourceV1, ()>
+    + Dispatch<ExtImageCopyCaptureManagerV1, ()>
+    + Dispatch<ExtImageCopyCaptureSessionV1, u64>
+    + Dispatch<ExtImageCopyCaptureFrameV1, u64>
+    + Sized
+    + 'static
+{
+    fn thumbnails(&mut self) -> &mut Thumbnails;
+
+    /// Called after a new capture of window `id` is available.
+    fn thumbnail_updated(&mut self, qh: &QueueHandle<Self>, id: u64);
+}
+
+/// A window the switcher wants a thumbnail of.
+pub struct ThumbnailTarget {
+    pub id: u64,
+    /// Handle for `hyprland_toplevel_export_manager_v1.capture_toplevel`.
+    pub export_handle: Option<u32>,
+    pub app_id: String,
+    pub title: Option<String>,
+}
+
+/// Captures of the switcher's windows, kept current while it is open.
+pub struct Thumbnails {
+    protocol: CaptureProtocol,
+    pool: SlotPool,
+    max_size: u32,
+    targets: Vec<ThumbnailTarget>,
+    captures: HashMap<u64, Capture>,
+    images: HashMap<u64, Arc<Pixmap>>,
+}
+
+enum CaptureProtocol {
+    Hyprland(HyprlandToplevelExportManagerV1),
+    Ext {
+        _list: ExtForeignToplevelListV1,
+        sources: ExtForeignToplevelImageCaptureSourceManagerV1,
+        manager: ExtImageCopyCaptureManagerV1,
+        toplevels: Vec<ListedToplevel>,
+    },
+}
+
+/// An `ext_foreign_toplevel_handle_v1`, matched to a target by app id and
+/// title since the protocol knows nothing about compositor window ids.
+struct ListedToplevel {
+    handle: ExtForeignToplevelHandleV1,
+    app_id: Option<String>,
+    title: Option<String>,
+    matched: bool,
+}
+
+#[derive(Default)]
+struct Capture {
+    export_handle: Option<u32>,
+    session: Option<ExtImageCopyCaptureSessionV1>,
+    _source: Option<ExtImageCaptureSourceV1>,
+    size: Option<(u32, u32)>,
+    stride: u32,
+    format: Option<wl_shm::Format>,
+    buffer: Option<Buffer>,
+    y_invert: bool,
+    in_flight: bool,
+    next_at: Option<Instant>,
+}
+
+impl Thumbnails {
+    /// Returns `None` when the compositor offers no way to capture windows,
+    /// in which case the switcher keeps showing icons.
+    pub fn new<D: ThumbnailHandler>(
+        globals: &GlobalList,
+        qh: &QueueHandle<D>,
+        shm: &Shm,
+        targets: Vec<ThumbnailTarget>,
+        max_size: u32,
+    ) -> Option<Self> {
+        let hyprland = targets
+            .iter()
+            .all(|target| target.export_handle.is_some())
+            .then(|| globals.bind::<HyprlandToplevelExportManagerV1, _, _>(qh, 1..=1, ()).ok())
+            .flatten();
+        let protocol = match hyprland {
+            Some(manager) => CaptureProtocol::Hyprland(manager),
+            None => {
+                let sources = globals
+                    .bind::<ExtForeignToplevelImageCaptureSourceManagerV1, _, _>(qh, 1..=1, ())
+                    .ok()?;
+                let manager = globals
+                    .bind::<ExtImageCopyCaptureManagerV1, _, _>(qh, 1..=1, ())
+                    .ok()?;
+                let list = globals
+                    .bind::<ExtForeignToplevelListV1, _, _>(qh, 1..=1, ())
+                    .ok()?;
+                CaptureProtocol::Ext {
+                    _list: list,
+                    sources,
+                    manager,
+                    toplevels: Vec::new(),
+                }
+            }
+        };
+        let pool = SlotPool::new(1, shm).ok()?;
+        let mut thumbnails = Self {
+            protocol,
+            pool,
+            max_size: max_size.max(1),
+            targets,
+            captures: HashMap::new(),
+            images: HashMap::new(),
+        };
+        if let CaptureProtocol::Hyprland(_) = thumbnails.protocol {
+            for target in &thumbnails.targets {
+                thumbnails.captures.insert(
+                    target.id,
+                    Capture {
+                        export_handle: target.export_handle,
+                        next_at: Some(Instant::now()),
+                        ..Capture::default()
+                    },
+                );
+            }
+            thumbnails.refresh(qh);
+        }
+        Some(thumbnails)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Pixmap>> {
+        self.images.get(&id).cloned()
+    }
+
+    /// When [`Self::refresh`] next has work to do.
+    pub fn next_refresh(&self) -> Option<Instant> {
+        self.captures
+            .values()
+            .filter(|capture| !capture.in_flight)
+            .filter_map(|capture| capture.next_at)
+            .min()
+    }
+
+    /// Starts the captures that are due.
+    pub fn refresh<D: ThumbnailHandler>(&mut self, qh: &QueueHandle<D>) {
+        let now = Instant::now();
+        let due = self
+            .captures
+            .iter()
+            .filter(|(_, capture)| !capture.in_flight)
+            .filter(|(_, capture)| capture.next_at.is_some_and(|at| at <= now))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in due {
+            self.start_capture(qh, id);
+        }
+    }
+
+    fn start_capture<D: ThumbnailHandler>(&mut self, qh: &QueueHandle<D>, id: u64) {
+        let Some(capture) = self.captures.get_mut(&id) else {
+            return;
+        };
+        capture.next_at = None;
+        match &self.protocol {
+            CaptureProtocol::Hyprland(manager) => {
+                let Some(handle) = capture.export_handle else {
+                    return;
+                };
+                capture.format = None;
+                capture.in_flight = true;
+                manager.capture_toplevel(0, handle, qh, id);
+            }
+            CaptureProtocol::Ext { .. } => {
+                let (Some(session), Some(buffer)) = (&capture.session, &capture.buffer) else {
+                    return;
+                };
+                let Some((width, height)) = capture.size else {
+                    return;
+                };
+                capture.in_flight = true;
+                let frame = session.create_frame(qh, id);
+                frame.attach_buffer(buffer.wl_buffer());
+                frame.damage_buffer(0, 0, width as i32, height as i32);
+                frame.capture();
+            }
+        }
+    }
+
+    /// Makes sure the capture has a buffer matching its negotiated size and
+    /// format.
+    fn prepare_buffer(&mut self, id: u64) -> bool {
+        let Some(capture) = self.captures.get_mut(&id) else {
+            return false;
+        };
+        let (Some((width, height)), Some(format)) = (capture.size, capture.format) else {
+            return false;
+        };
+        if capture.stride == 0 {
+            capture.stride = width * 4;
+        }
+        let matches = capture.buffer.as_ref().is_some_and(|buffer| {
+            buffer.height() == height as i32 && buffer.stride() == capture.stride as i32
+        });
+        if !matches {
+            capture.buffer = self
+                .pool
+                .create_buffer(width as i32, height as i32, capture.stride as i32, format)
+                .ok()
+                .map(|(buffer, _)| buffer);
+        }
+        capture.buffer.is_some()
+    }
+
+    /// Reads a finished capture into a thumbnail-sized pixmap.
+    fn finish_capture(&mut self, id: u64) -> bool {
+        let Some(capture) = self.captures.get_mut(&id) else {
+            return false;
+        };
+        capture.in_flight = false;
+        capture.next_at = Some(Instant::now() + REFRESH_INTERVAL);
+        let (Some(buffer), Some((width, height)), Some(format)) =
+            (&capture.buffer, capture.size, capture.format)
+        else {
+            return false;
+        };
+        let Some(data) = buffer.canvas(&mut self.pool) else {
+            return false;
+        };
+        let image = scale_capture(
+            data,
+            width,
+            height,
+            capture.stride,
+            format,
+            capture.y_invert,
+            self.max_size,
+        );
+        match image {
+            Some(image) => {
+                self.images.insert(id, Arc::new(image));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn fail_capture(&mut self, id: u64, retry: bool) {
+        if let Some(capture) = self.captures.get_mut(&id) {
+            capture.in_flight = false;
+            capture.next_at = retry.then(|| Instant::now() + REFRESH_INTERVAL);
+        }
+    }
+
+    fn match_toplevel<D: ThumbnailHandler>(
+        &mut self,
+        qh: &QueueHandle<D>,
+        handle: &ExtForeignToplevelHandleV1,
+    ) {
+        let CaptureProtocol::Ext {
+            sources,
+            manager,
+            toplevels,
+            ..
+        } = &mut self.protocol
+        else {
+            return;
+        };
+        let Some(toplevel) = toplevels
+            .iter_mut()
+            .find(|toplevel| &toplevel.handle == handle && !toplevel.matched)
+        else {
+            return;
+        };
+        let captures = &self.captures;
+        let Some(target) = self.targets.iter().find(|target| {
+            !captures.contains_key(&target.id)
+                && toplevel
+                    .app_id
+                    .as_deref()
+                    .is_some_and(|app_id| app_id.eq_ignore_ascii_case(&target.app_id))
+                && toplevel.title == target.title
+        }) else {
+            return;
+        };
+        toplevel.matched = true;
+        let source = sources.create_source(handle, qh, ());
+        let session = manager.create_session(
+            &source,
+            ext_image_copy_capture_manager_v1::Options::empty(),
+            qh,
+            target.id,
+        );
+        self.captures.insert(
+            target.id,
+            Capture {
+                session: Some(session),
+                _source: Some(source),
+                ..Capture::default()
+            },
+        );
+    }
+}
+
+impl Drop for Thumbnails {
+    fn drop(&mut self) {
+        for capture in self.captures.values() {
+            if let Some(session) = &capture.session {
+                session.destroy();
+            }
+        }
+    }
+}
+
+/// Converts a captured shm buffer to an RGBA pixmap that fits in a
+/// `max_size` square, averaging the source pixels behind each target pixel.
+fn scale_capture(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    stride: u32,
+    format: wl_shm::Format,
+    y_invert: bool,
+    max_size: u32,
+) -> Option<Pixmap> {
+    let (red, blue, opaque) = match format {
+        wl_shm::Format::Argb8888 => (2, 0, false),
+        wl_shm::Format::Xrgb8888 => (2, 0, true),
+        wl_shm::Format::Abgr8888 => (0, 2, false),
+        wl_shm::Format::Xbgr8888 => (0, 2, true),
+        _ => return None,
+    };
+    if width == 0 || height == 0 || data.len() < (stride * height) as usize {
+        return None;
+    }
+    let factor = (max_size as f32 / width.max(height) as f32).min(1.0);
+    let target_width = ((width as f32 * factor).round() as u32).max(1);
+    let target_height = ((height as f32 * factor).round() as u32).max(1);
+    let mut pixmap = Pixmap::new(target_width, target_height)?;
+    let out = pixmap.data_mut();
+    for ty in 0..target_height {
+        let y0 = ty * height / target_height;
+        let y1 = ((ty + 1) * height / target_height).max(y0 + 1);
+        for tx in 0..target_width {
+            let x0 = tx * width / target_width;
+            let x1 = ((tx + 1) * width / target_width).max(x0 + 1);
+            let mut sum = [0u32; 4];
+            for y in y0..y1 {
+                let row = if y_invert { height - 1 - y } else { y };
+                let row = &data[(row * stride) as usize..];
+                for x in x0..x1 {
+                    let pixel = &row[(x * 4) as usize..(x * 4 + 4) as usize];
+                    sum[0] += pixel[red] as u32;
+                    sum[1] += pixel[1] as u32;
+                    sum[2] += pixel[blue] as u32;
+                    sum[3] += if opaque { 255 } else { pixel[3] as u32 };
+                }
+            }
+            let count = (y1 - y0) * (x1 - x0);
+            let offset = ((ty * target_width + tx) * 4) as usize;
+            let alpha = sum[3] / count;
+            for channel in 0..3 {
+                out[offset + channel] = (sum[channel] / count).min(alpha) as u8;
+            }
+            out[offset + 3] = alpha as u8;
+        }
+    }
+    Some(pixmap)
+}
+
+fn preferred_format(current: Option<wl_shm::Format>, offered: wl_shm::Format) -> Option<wl_shm::Format> {
+    let rank = |format: wl_shm::Format| FORMATS.iter().position(|&known| known == format);
+    match (current, rank(offered)) {
+        (_, None) => current,
+        (None, Some(_)) => Some(offered),
+        (Some(current), Some(offered_rank)) => {
+            if rank(current).is_none_or(|current_rank| offered_rank < current_rank) {
+                Some(offered)
+            } else {
+                Some(current)
+            }
+        }
+    }
+}
+
+impl<D: ThumbnailHandler> Dispatch<HyprlandToplevelExportManagerV1, (), D> for Thumbnails {
+    fn event(
+        _state: &mut D,
+        _manager: &HyprlandToplevelExportManagerV1,
+        _event: <HyprlandToplevelExportManagerV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<D>,
+    ) {
+    }
+}
+
+impl<D: ThumbnailHandler> Dispatch<HyprlandToplevelExportFrameV1, u64, D> for Thumbnails {
+    fn event(
+        state: &mut D,
+        frame: &HyprlandToplevelExportFrameV1,
+        event: hyprland_toplevel_export_frame_v1::Event,
+        id: &u64,
+        _conn: &Connection,
+        qh: &QueueHandle<D>,
+    ) {
+        let id = *id;
+        let thumbnails = state.thumbnails();
+        match event {
+            hyprland_toplevel_export_frame_v1::Event::Buffer {
+                format: WEnum::Value(format),
+                width,
+                height,
+                stride,
+            } => {
+                if let Some(capture) = thumbnails.captures.get_mut(&id) {
+                    let format = preferred_format(capture.format, format);
+                    if format != capture.format {
+                        capture.format = format;
+                        capture.size = Some((width, height));
+                        capture.stride = stride;
+                    }
+                }
+            }
+            hyprland_toplevel_export_frame_v1::Event::Flags {
+                flags: WEnum::Value(flags),
+            } => {
+                if let Some(capture) = thumbnails.captures.get_mut(&id) {
+                    capture.y_invert =
+                        flags.contains(hyprland_toplevel_export_frame_v1::Flags::YInvert);
+                }
+            }
+            hyprland_toplevel_export_frame_v1::Event::BufferDone => {
+                if thumbnails.prepare_buffer(id)
+                    && let Some(buffer) = thumbnails
+                        .captures
+                        .get(&id)
+                        .and_then(|capture| capture.buffer.as_ref())
+                {
+                    frame.copy(buffer.wl_buffer(), 1);
+                } else {
+                    frame.destroy();
+                    thumbnails.fail_capture(id, false);
+                }
+            }
+            hyprland_toplevel_export_frame_v1::Event::Ready { .. } => {
+                frame.destroy();
+                if thumbnails.finish_capture(id) {
+                    state.thumbnail_updated(qh, id);
+                }
+            }
+            hyprland_toplevel_export_frame_v1::Event::Failed => {
+                frame.destroy();
+                thumbnails.fail_capture(id, true);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<D: ThumbnailHandler> Dispatch<ExtForeignToplevelListV1, (), D> for Thumbnails {
+    fn event(
+        state: &mut D,
+        _list: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<D>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event
+            && let CaptureProtocol::Ext { toplevels, .. } = &mut state.thumbnails().protocol
+        {
+            toplevels.push(ListedToplevel {
+                handle: toplevel,
+                app_id: None,
+                title: None,
+                matched: false,
+            });
+        }
+    }
+
+    event_created_child!(D, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl<D: ThumbnailHandler> Dispatch<ExtForeignToplevelHandleV1, (), D> for Thumbnails {
+    fn event(
+        state: &mut D,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<D>,
+    ) {
+        let thumbnails = state.thumbnails();
+        if let ext_foreign_toplevel_handle_v1::Event::Done = event {
+            thumbnails.match_toplevel(qh, handle);
+            return;
+        }
+        let CaptureProtocol::Ext { toplevels, .. } = &mut thumbnails.protocol else {
+            return;
+        };
+        let Some(toplevel) = toplevels
+            .iter_mut()
+            .find(|toplevel| &toplevel.handle == handle)
+        else {
+            return;
+        };
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = Some(title),
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                toplevel.app_id = Some(app_id)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<D: ThumbnailHandler> Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, (), D>
+    for Thumbnails
+{
+    fn event(
+        _state: &mut D,
+        _manager: &ExtForeignToplevelImageCaptureSourceManagerV1,
+        _event: <ExtForeignToplevelImageCaptureSourceManagerV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<D>,
+    ) {
+    }
+}
+
+impl<D: ThumbnailHandler> Dispatch<ExtImageCaptureSourceV1, (), D> for Thumbnails {
+    fn event(
+        _state: &mut D,
+        _source: &ExtImageCaptureSourceV1,
+        _event: <ExtImageCaptureSourceV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<D>,
+    ) {
+    }
+}
+
+impl<D: ThumbnailHandler> Dispatch<ExtImageCopyCaptureManagerV1, (), D> for Thumbnails {
+    fn event(
+        _state: &mut D,
+        _manager: &ExtImageCopyCaptureManagerV1,
+        _event: <ExtImageCopyCaptureManagerV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<D>,
+    ) {
+    }
+}
+
+impl<D: ThumbnailHandler> Dispatch<ExtImageCopyCaptureSessionV1, u64, D> for Thumbnails {
+    fn event(
+        state: &mut D,
+        _session: &ExtImageCopyCaptureSessionV1,
+        event: ext_image_copy_capture_session_v1::Event,
+        id: &u64,
+        _conn: &Connection,
+        qh: &QueueHandle<D>,
+    ) {
+        let id = *id;
+        let thumbnails = state.thumbnails();
+        let Some(capture) = thumbnails.captures.get_mut(&id) else {
+            return;
+        };
+        match event {
+            ext_image_copy_capture_session_v1::Event::BufferSize { width, height } => {
+                capture.size = Some((width, height));
+                capture.stride = 0;
+                capture.format = None;
+            }
+            ext_image_copy_capture_session_v1::Event::ShmFormat {
+                format: WEnum::Value(format),
+            } => {
+                capture.format = preferred_format(capture.format, format);
+            }
+            ext_image_copy_capture_session_v1::Event::Done => {
+                let first = capture.buffer.is_none() && !capture.in_flight;
+                if thumbnails.prepare_buffer(id) && first {
+                    thumbnails.start_capture(qh, id);
+                }
+            }
+            ext_image_copy_capture_session_v1::Event::Stopped => {
+                if let Some(session) = capture.session.take() {
+                    session.destroy();
+                }
+                capture.in_flight = false;
+                capture.next_at = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<D: ThumbnailHandler> Dispatch<ExtImageCopyCaptureFrameV1, u64, D> for Thumbnails {
+    fn event(
+        state: &mut D,
+        frame: &ExtImageCopyCaptureFrameV1,
+        event: ext_image_copy_capture_frame_v1::Event,
+        id: &u64,
+        _conn: &Connection,
+        qh: &QueueHandle<D>,
+    ) {
+        let id = *id;
+        let thumbnails = state.thumbnails();
+        match event {
+            ext_image_copy_capture_frame_v1::Event::Transform { transform } => {
+                if let Some(capture) = thumbnails.captures.get_mut(&id) {
+                    capture.y_invert = matches!(
+                        transform,
+                        WEnum::Value(wayland_client::protocol::wl_output::Transform::Flipped180)
+                    );
+                }
+            }
+            ext_image_copy_capture_frame_v1::Event::Ready => {
+                frame.destroy();
+                if thumbnails.finish_capture(id) {
+                    state.thumbnail_updated(qh, id);
+                }
+            }
+            ext_image_copy_capture_frame_v1::Event::Failed { reason } => {
+                frame.destroy();
+                let stopped = matches!(
+                    reason,
+                    WEnum::Value(ext_image_copy_capture_frame_v1::FailureReason::Stopped)
+                );
+                thumbnails.fail_capture(id, !stopped);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Forwards the thumbnail protocol objects of `$ty` to [`Thumbnails`].
+macro_rules! delegate_thumbnails {
+    ($ty:ty) => {
+        wayland_client::delegate_dispatch!($ty: [
+            $crate::thumbnail::hyprland_export::hyprland_toplevel_export_manager_v1::HyprlandToplevelExportManagerV1: ()
+        ] => $crate::thumbnail::Thumbnails);
+        wayland_client::delegate_dispatch!($ty: [
+            $crate::thumbnail::hyprland_export::hyprland_toplevel_export_frame_v1::HyprlandToplevelExportFrameV1: u64
+        ] => $crate::thumbnail::Thumbnails);
+        wayland_client::delegate_dispatch!($ty: [
+            wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1: ()
+        ] => $crate::thumbnail::Thumbnails);
+        wayland_client::delegate_dispatch!($ty: [
+            wayland_protocols::ext::foreign_toplevel_list::v1::client::ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1: ()
+        ] => $crate::thumbnail::Thumbnails);
+        wayland_client::delegate_dispatch!($ty: [
+            wayland_protocols::ext::image_capture_source::v1::client::ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1: ()
+        ] => $crate::thumbnail::Thumbnails);
+        wayland_client::delegate_dispatch!($ty: [
+            wayland_protocols::ext::image_capture_source::v1::client::ext_image_capture_source_v1::ExtImageCaptureSourceV1: ()
+        ] => $crate::thumbnail::Thumbnails);
+        wayland_client::delegate_dispatch!($ty: [
+            wayland_protocols::ext::image_copy_capture::v1::client::ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1: ()
+        ] => $crate::thumbnail::Thumbnails);
+        wayland_client::delegate_dispatch!($ty: [
+            wayland_protocols::ext::image_copy_capture::v1::client::ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1: u64
+        ] => $crate::thumbnail::Thumbnails);
+        wayland_client::delegate_dispatch!($ty: [
+            wayland_protocols::ext::image_copy_capture::v1::client::ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1: u64
+        ] => $crate::thumbnail::Thumbnails);
+    };
+}
+pub(crate) use delegate_thumbnails;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_is_converted_and_averaged_down() {
+        // 4x2 Xrgb8888 capture, stored upside down: a red and a blue half.
+        let mut data = Vec::new();
+        for _ in 0..4 {
+            data.extend_from_slice(&[255, 0, 0, 0]);
+        }
+        for _ in 0..4 {
+            data.extend_from_slice(&[0, 0, 255, 0]);
+        }
+        let pixmap =
+            scale_capture(&data, 4, 2, 16, wl_shm::Format::Xrgb8888, true, 2).expect("pixmap");
+        assert_eq!((pixmap.width(), pixmap.height()), (2, 1));
+        let pixel = pixmap.pixel(0, 0).unwrap();
+        assert_eq!(
+            (pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()),
+            (127, 0, 127, 255)
+        );
+
+        assert_eq!(
+            preferred_format(Some(wl_shm::Format::Xbgr8888), wl_shm::Format::Argb8888),
+            Some(wl_shm::Format::Argb8888)
+        );
+        assert_eq!(
+            preferred_format(Some(wl_shm::Format::Xrgb8888), wl_shm::Format::Rgb565),
+            Some(wl_shm::Format::Xrgb8888)
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tiny_skia::Pixmap;
 
@@ -8,11 +9,81 @@ pub enum BackendKind {
     Hyprland,
     Kwin,
     Gnome,
+    ForeignToplevel,
+}
+
+impl BackendKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            BackendKind::Niri => "niri",
+            BackendKind::Sway => "sway",
+            BackendKind::Hyprland => "hyprland",
+            BackendKind::Kwin => "kwin",
+            BackendKind::Gnome => "gnome",
+            BackendKind::ForeignToplevel => "foreign-toplevel",
+        }
+    }
+}
+
+/// Which windows the switcher lists, relative to where the user is.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowFilter {
+    #[default]
+    All,
+    CurrentWorkspace,
+    CurrentOutput,
+}
+
+impl WindowFilter {
+    pub fn name(self) -> &'static str {
+        match self {
+            WindowFilter::All => "all",
+            WindowFilter::CurrentWorkspace => "current-workspace",
+            WindowFilter::CurrentOutput => "current-output",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "all" => Some(WindowFilter::All),
+            "current-workspace" => Some(WindowFilter::CurrentWorkspace),
+            "current-output" => Some(WindowFilter::CurrentOutput),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct WindowEntry {
     pub id: u64,
     pub is_focused: bool,
+    pub app_id: String,
+    /// Name from the app's desktop entry.
+    pub app_name: Option<String>,
+    pub title: Option<String>,
     pub icon: Arc<Pixmap>,
+    /// Listed ahead of the other windows, by a `pin_first` rule.
+    pub pinned: bool,
+    /// The app's other windows, most recent first, when windows are grouped
+    /// by app and this entry stands for the whole app.
+    pub grouped: Vec<WindowEntry>,
+}
+
+impl WindowEntry {
+    /// How many windows the tile stands for.
+    pub fn window_count(&self) -> usize {
+        1 + self.grouped.len()
+    }
+
+    /// The tile's windows as separate entries, most recent first.
+    pub fn ungroup(&self) -> Vec<WindowEntry> {
+        let mut windows = Vec::with_capacity(self.window_count());
+        windows.push(WindowEntry {
+            grouped: Vec::new(),
+            ..self.clone()
+        });
+        windows.extend(self.grouped.iter().cloned());
+        windows
+    }
 }